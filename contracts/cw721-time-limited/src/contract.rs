@@ -1,16 +1,36 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw721::Cw721Query;
+use cw_utils::Duration;
 
-use crate::execute::{approve, approve_all, burn, revoke, revoke_all, send_nft, transfer_nft};
-use crate::msg::{Cw2981QueryMsg, ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::query::{check_royalties, contract_info, query_royalties_info};
-use crate::state::{Config, Cw721TimeLimited, CONFIG, CREATOR};
+use crate::error::ContractError;
+use crate::execute::{
+    activate, apply_royalty_config, approve, approve_all, burn, cleanup_expired, consume,
+    extend_expiration, freeze_expiry, fund_bounty_pool, index_expirations, mint, receive,
+    reclaim_expired, redeem, renew, revoke, revoke_all, send_nft, transfer_nft, unfreeze_expiry,
+    update_consumers, update_lifetime_config, update_royalty_config, update_scanners,
+    validate_lifetime_config, validate_royalty_recipients,
+};
+use crate::msg::{
+    Cw2981QueryMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, TimeLimitedQueryMsg,
+};
+use crate::query::{
+    check_royalties, contract_info, query_all_nft_info, query_all_tokens, query_all_visible_tokens,
+    query_consumers, query_expired, query_expiring_before, query_lifetime_config, query_nft_info,
+    query_owner_of, query_post_expiry_policy, query_redemption, query_royalties_info,
+    query_royalty_config, query_royalty_splits, query_scanners, query_token_status, query_tokens,
+    query_visible_tokens,
+};
+use crate::state::{
+    Config, Cw721TimeLimited, LifetimeConfig, PostExpiryPolicy, CONFIG, CREATOR,
+    DEFAULT_ROYALTY_TIMELOCK, INDEX_BACKFILL, MAX_ROYALTY_BPS,
+};
 
-use cw721_base::{ContractError, InstantiateMsg as Cw721InstantiateMsg};
+use cw721_base::InstantiateMsg as Cw721InstantiateMsg;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw721-time-limited";
@@ -36,21 +56,111 @@ pub fn instantiate(
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)
         .map_err(ContractError::Std)?;
 
-    // validate royalty_percentage to be between 0 and 100
-    if let Some(royalty_percentage) = msg.royalty_percentage {
-        if royalty_percentage > 100 {
+    // validate royalty_bps to be between 0 and 10000
+    if let Some(royalty_bps) = msg.royalty_bps {
+        if royalty_bps > MAX_ROYALTY_BPS {
             return Err(ContractError::Std(StdError::generic_err(
-                "Royalty percentage cannot be greater than 100",
+                "Royalty cannot be greater than 10000 basis points",
             )));
         }
     }
+    // the cap on later rate changes must allow the initial rate,
+    // without one the rate can only be lowered
+    let max_royalty_bps = msg
+        .max_royalty_bps
+        .unwrap_or(msg.royalty_bps.unwrap_or_default());
+    if max_royalty_bps > MAX_ROYALTY_BPS || msg.royalty_bps.unwrap_or_default() > max_royalty_bps {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Max royalty must be between the royalty and 10000 basis points",
+        )));
+    }
+    // rate changes always wait for a timelock
+    let royalty_timelock = msg.royalty_timelock.unwrap_or(DEFAULT_ROYALTY_TIMELOCK);
+    if matches!(royalty_timelock, Duration::Height(0) | Duration::Time(0)) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Royalty timelock must be non-zero",
+        )));
+    }
+
+    // a renewal price needs a non-zero amount and a period to be bought
+    if let Some(renewal_price) = &msg.renewal_price {
+        if renewal_price.amount.is_zero() || msg.renewal_period.is_none() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Renewal price must be non-zero and come with a renewal period",
+            )));
+        }
+    }
+    let treasury = msg
+        .treasury
+        .map(|treasury| deps.api.addr_validate(&treasury))
+        .transpose()?;
+    let reclaim_vault = msg
+        .reclaim_vault
+        .map(|reclaim_vault| deps.api.addr_validate(&reclaim_vault))
+        .transpose()?;
+    let cw20_prices = msg
+        .cw20_prices
+        .unwrap_or_default()
+        .into_iter()
+        .map(|price| {
+            deps.api.addr_validate(&price.address)?;
+            // a zero amount would be sent on as a transfer the CW20 contract rejects
+            if price.mint_amount == Some(Uint128::zero())
+                || price.renewal_amount == Some(Uint128::zero())
+            {
+                return Err(StdError::generic_err("CW20 prices must be non-zero"));
+            }
+            Ok(price)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    // expired tokens can only revert to a creator if there is one they can be sent to
+    let post_expiry = msg.post_expiry.unwrap_or_default();
+    if post_expiry == PostExpiryPolicy::RevertToCreator {
+        match &msg.creator {
+            Some(creator) => {
+                deps.api.addr_validate(creator)?;
+            }
+            None => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Reverting expired tokens to the creator requires a creator",
+                )))
+            }
+        }
+    }
+    let lifetime = msg.lifetime.unwrap_or_default();
+    validate_lifetime_config(&lifetime)?;
+    let royalty_recipients = msg.royalty_recipients.unwrap_or_default();
+    if !royalty_recipients.is_empty() {
+        validate_royalty_recipients(deps.api, &royalty_recipients)?;
+    }
 
-    // set royalty_percentage and royalty_payment_address
+    // set royalty_bps, royalty_payment_address and the renewal settings
     CONFIG.save(
         deps.storage,
         &Config {
-            royalty_percentage: msg.royalty_percentage,
+            royalty_bps: msg.royalty_bps,
+            royalty_percentage: None,
             royalty_payment_address: msg.royalty_payment_address,
+            max_royalty_bps: Some(max_royalty_bps),
+            royalty_timelock: Some(royalty_timelock),
+            pending_royalty: None,
+            renewal_period: msg.renewal_period,
+            renewal_price: msg.renewal_price,
+            treasury,
+            cw20_prices,
+            grace_period: msg.grace_period,
+            reclaim_vault,
+            post_expiry,
+            cleanup_bounty: msg.cleanup_bounty,
+            expired_visibility: msg.expired_visibility.unwrap_or_default(),
+            lifetime,
+            allowed_expiration: msg.allowed_expiration.unwrap_or_default(),
+            pending_transfers: msg.pending_transfers.unwrap_or_default(),
+            not_yet_valid_transfers: msg.not_yet_valid_transfers.unwrap_or_default(),
+            transferability: msg.transferability.unwrap_or_default(),
+            expired_token_uri: msg.expired_token_uri,
+            expired_image: msg.expired_image,
+            royalty_recipients,
         },
     )?;
 
@@ -73,31 +183,10 @@ pub fn execute(
             owner,
             token_uri,
             extension,
-        } => {
-            let mut extension = extension.unwrap_or_default();
-
-            // return error if royalty is set
-            if extension.royalty_percentage.is_some() || extension.royalty_payment_address.is_some()
-            {
-                return Err(ContractError::Std(StdError::generic_err(
-                    "Cannot set royalty information in mint message",
-                )));
-            }
-
-            // override royalty information with config
-            let config = CONFIG.load(deps.storage)?;
-            extension.royalty_percentage = config.royalty_percentage;
-            extension.royalty_payment_address = config.royalty_payment_address;
-
-            let mint_msg = ExecuteMsg::Mint {
-                token_id,
-                owner,
-                token_uri,
-                extension: Some(extension),
-            };
-
-            Cw721TimeLimited::default().execute(deps, env, info, mint_msg)
-        }
+            lifetime,
+        } => mint(
+            deps, env, info, token_id, owner, token_uri, extension, lifetime,
+        ),
         ExecuteMsg::Approve {
             spender,
             token_id,
@@ -118,12 +207,59 @@ pub fn execute(
             msg,
         } => send_nft(deps, env, info, contract, token_id, msg),
         ExecuteMsg::Burn { token_id } => burn(deps, env, info, token_id),
-        _ => Cw721TimeLimited::default().execute(deps, env, info, msg),
+        ExecuteMsg::ExtendExpiration { token_id, expires } => {
+            extend_expiration(deps, env, info, token_id, expires)
+        }
+        ExecuteMsg::FreezeExpiry { token_id } => freeze_expiry(deps, env, info, token_id),
+        ExecuteMsg::UnfreezeExpiry { token_id } => unfreeze_expiry(deps, env, info, token_id),
+        ExecuteMsg::Renew { token_id } => renew(deps, env, info, token_id),
+        ExecuteMsg::Activate { token_id } => activate(deps, env, info, token_id),
+        ExecuteMsg::Receive(msg) => receive(deps, env, info, msg),
+        ExecuteMsg::ReclaimExpired { token_ids, limit } => {
+            reclaim_expired(deps, env, info, token_ids, limit)
+        }
+        ExecuteMsg::FundBountyPool {} => fund_bounty_pool(deps, env, info),
+        ExecuteMsg::CleanupExpired { token_ids } => cleanup_expired(deps, env, info, token_ids),
+        ExecuteMsg::IndexExpirations { limit } => index_expirations(deps, env, info, limit),
+        ExecuteMsg::Consume { token_id, amount } => consume(deps, env, info, token_id, amount),
+        ExecuteMsg::UpdateConsumers { add, remove } => update_consumers(deps, info, add, remove),
+        ExecuteMsg::Redeem { token_id } => redeem(deps, env, info, token_id),
+        ExecuteMsg::UpdateScanners { add, remove } => update_scanners(deps, info, add, remove),
+        ExecuteMsg::UpdateRoyaltyConfig {
+            royalty_bps,
+            royalty_payment_address,
+            royalty_recipients,
+        } => update_royalty_config(
+            deps,
+            env,
+            info,
+            royalty_bps,
+            royalty_payment_address,
+            royalty_recipients,
+        ),
+        ExecuteMsg::ApplyRoyaltyConfig {} => apply_royalty_config(deps, env, info),
+        ExecuteMsg::UpdateLifetimeConfig {
+            default_lifetime,
+            min_lifetime,
+            max_lifetime,
+        } => update_lifetime_config(
+            deps,
+            info,
+            LifetimeConfig {
+                default_lifetime,
+                min_lifetime,
+                max_lifetime,
+            },
+        ),
+        ExecuteMsg::UpdateOwnership(action) => {
+            Ok(Cw721TimeLimited::update_ownership(deps, env, info, action)?)
+        }
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    let contract = Cw721TimeLimited::default();
     match msg {
         QueryMsg::Extension { msg } => match msg {
             Cw2981QueryMsg::RoyaltyInfo {
@@ -132,7 +268,166 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             } => to_binary(&query_royalties_info(deps, token_id, sale_price)?),
             Cw2981QueryMsg::CheckRoyalties {} => to_binary(&check_royalties(deps)?),
         },
+        QueryMsg::TimeLimited { msg } => match msg {
+            TimeLimitedQueryMsg::ExpiringBefore {
+                at,
+                start_after,
+                limit,
+            } => to_binary(&query_expiring_before(deps, at, start_after, limit)?),
+            TimeLimitedQueryMsg::Expired { start_after, limit } => {
+                to_binary(&query_expired(deps, env, start_after, limit)?)
+            }
+            TimeLimitedQueryMsg::TokenStatus { token_id } => {
+                to_binary(&query_token_status(deps, env, token_id)?)
+            }
+            TimeLimitedQueryMsg::LifetimeConfig {} => to_binary(&query_lifetime_config(deps)?),
+            TimeLimitedQueryMsg::PostExpiryPolicy {} => to_binary(&query_post_expiry_policy(deps)?),
+            TimeLimitedQueryMsg::Consumers { start_after, limit } => {
+                to_binary(&query_consumers(deps, start_after, limit)?)
+            }
+            TimeLimitedQueryMsg::Scanners { start_after, limit } => {
+                to_binary(&query_scanners(deps, start_after, limit)?)
+            }
+            TimeLimitedQueryMsg::Redemption { token_id } => {
+                to_binary(&query_redemption(deps, token_id)?)
+            }
+            TimeLimitedQueryMsg::VisibleTokens {
+                owner,
+                start_after,
+                limit,
+            } => to_binary(&query_visible_tokens(deps, env, owner, start_after, limit)?),
+            TimeLimitedQueryMsg::AllVisibleTokens { start_after, limit } => {
+                to_binary(&query_all_visible_tokens(deps, env, start_after, limit)?)
+            }
+        },
         QueryMsg::ContractInfo {} => to_binary(&contract_info(deps)?),
-        _ => Cw721TimeLimited::default().query(deps, env, msg),
+        QueryMsg::OwnerOf {
+            token_id,
+            include_expired,
+        } => to_binary(&query_owner_of(
+            deps,
+            env,
+            token_id,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::AllNftInfo {
+            token_id,
+            include_expired,
+        } => to_binary(&query_all_nft_info(
+            deps,
+            env,
+            token_id,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::Tokens {
+            owner,
+            start_after,
+            limit,
+            include_expired,
+        } => to_binary(&query_tokens(
+            deps,
+            env,
+            owner,
+            start_after,
+            limit,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::AllTokens {
+            start_after,
+            limit,
+            include_expired,
+        } => to_binary(&query_all_tokens(
+            deps,
+            env,
+            start_after,
+            limit,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::NftInfo { token_id } => to_binary(&query_nft_info(deps, env, token_id)?),
+        QueryMsg::Approval {
+            token_id,
+            spender,
+            include_expired,
+        } => to_binary(&contract.approval(
+            deps,
+            env,
+            token_id,
+            spender,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::Approvals {
+            token_id,
+            include_expired,
+        } => {
+            to_binary(&contract.approvals(deps, env, token_id, include_expired.unwrap_or(false))?)
+        }
+        QueryMsg::Operator {
+            owner,
+            operator,
+            include_expired,
+        } => to_binary(&contract.operator(
+            deps,
+            env,
+            owner,
+            operator,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::AllOperators {
+            owner,
+            include_expired,
+            start_after,
+            limit,
+        } => to_binary(&contract.operators(
+            deps,
+            env,
+            owner,
+            include_expired.unwrap_or(false),
+            start_after,
+            limit,
+        )?),
+        QueryMsg::NumTokens {} => to_binary(&contract.num_tokens(deps)?),
+        QueryMsg::Minter {} => to_binary(&contract.minter(deps)?),
+        QueryMsg::RoyaltyConfig {} => to_binary(&query_royalty_config(deps)?),
+        QueryMsg::RoyaltySplits {
+            token_id,
+            sale_price,
+        } => to_binary(&query_royalty_splits(deps, token_id, sale_price)?),
+        QueryMsg::Ownership {} => to_binary(&Cw721TimeLimited::ownership(deps)?),
     }
 }
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version = get_contract_version(deps.storage)?;
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Cannot migrate from {}",
+            version.contract
+        ))));
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // move whole percent royalties into basis points, royalties of
+    // tokens are paid as the config says so they are left as they are
+    let mut config = CONFIG.load(deps.storage)?;
+    if let Some(royalty_percentage) = config.royalty_percentage.take() {
+        config.royalty_bps = Some(royalty_percentage * 100);
+    }
+    // collections from before rate changes cannot raise their rate
+    // and changes wait for the default timelock
+    config
+        .max_royalty_bps
+        .get_or_insert(config.royalty_bps.unwrap_or_default());
+    config
+        .royalty_timelock
+        .get_or_insert(DEFAULT_ROYALTY_TIMELOCK);
+    CONFIG.save(deps.storage, &config)?;
+
+    // tokens from before the expiration index are indexed by IndexExpirations
+    INDEX_BACKFILL.save(deps.storage, &None)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", version.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
@@ -0,0 +1,139 @@
+use cosmwasm_std::StdError;
+use cw_ownable::OwnershipError;
+use cw_utils::PaymentError;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error(transparent)]
+    Std(#[from] StdError),
+
+    #[error(transparent)]
+    Ownership(#[from] OwnershipError),
+
+    #[error(transparent)]
+    Base(#[from] cw721_base::ContractError),
+
+    #[error(transparent)]
+    Payment(#[from] PaymentError),
+
+    #[error("token_id already claimed")]
+    Claimed {},
+
+    #[error("Cannot set approval that is already expired")]
+    Expired {},
+
+    #[error("Token {token_id} does not expire")]
+    NoExpiration { token_id: String },
+
+    #[error("Cannot mint a token that is already expired")]
+    AlreadyExpired {},
+
+    #[error("Expiration {expires} is not of a kind allowed in this collection")]
+    ExpirationKindNotAllowed { expires: String },
+
+    #[error("Only one of an expiration, a lifetime and a pending lifetime can be set")]
+    ConflictingExpiration {},
+
+    #[error("New expiration must be of the same kind as the current one")]
+    ExpirationKindMismatch {},
+
+    #[error("New expiration must be later than the current one")]
+    ExpirationNotExtended {},
+
+    #[error("Token lifetime must be at least {min}")]
+    LifetimeTooShort { min: String },
+
+    #[error("Token lifetime must be at most {max}")]
+    LifetimeTooLong { max: String },
+
+    #[error("Token expiration must be of the same kind as the lifetime bound {bound}")]
+    LifetimeKindMismatch { bound: String },
+
+    #[error("Token {token_id} is not waiting for activation")]
+    NotPendingActivation { token_id: String },
+
+    #[error("Token must be activated by its owner before it can be moved")]
+    TokenNotActivated {},
+
+    #[error("Token cannot be moved before its validity window starts")]
+    TokenNotYetValid {},
+
+    #[error("Validity window must start before the token expires")]
+    InvalidValidityWindow {},
+
+    #[error("Expiry clock of token {token_id} is already frozen")]
+    AlreadyFrozen { token_id: String },
+
+    #[error("Expiry clock of token {token_id} is not frozen")]
+    NotFrozen { token_id: String },
+
+    #[error("Token {token_id} has no usage limit")]
+    NoUsageLimit { token_id: String },
+
+    #[error("Token has only {uses_remaining} uses left")]
+    NotEnoughUses { uses_remaining: u32 },
+
+    #[error("Token is not usable in its current state")]
+    TokenNotUsable {},
+
+    #[error("Sender is not an authorized scanner")]
+    NotScanner {},
+
+    #[error("Token {token_id} has already been redeemed")]
+    AlreadyRedeemed { token_id: String },
+
+    #[error("Token is bound to its owner and cannot be transferred or approved")]
+    NonTransferable {},
+
+    #[error("Expired token can only be handled as the post expiry policy says")]
+    PostExpiryRestricted {},
+
+    #[error("Expired tokens stay with their holders in this collection")]
+    ExpiredTokensKept {},
+
+    #[error("Only the creator or the minter can change the royalty config")]
+    NotCreatorOrMinter {},
+
+    #[error("Royalty cannot be greater than {max} basis points")]
+    RoyaltyAboveMax { max: u64 },
+
+    #[error("Royalty shares must add up to 10000 basis points, not {total}")]
+    RoyaltySharesMismatch { total: u64 },
+
+    #[error("There is no pending royalty change")]
+    NoPendingRoyalty {},
+
+    #[error("Royalty change cannot be applied before {applicable_at}")]
+    RoyaltyTimelocked { applicable_at: String },
+
+    #[error("Token {token_id} is expired")]
+    TokenExpired { token_id: String },
+
+    #[error("Token is in its grace period and can only be renewed")]
+    TokenInGracePeriod {},
+
+    #[error("Token {token_id} is not expired")]
+    TokenNotExpired { token_id: String },
+
+    #[error("Token {token_id} has already been cleaned up")]
+    AlreadyCleanedUp { token_id: String },
+
+    #[error("Cleanup bounties are not enabled for this collection")]
+    BountyDisabled {},
+
+    #[error("Self-service renewal is not enabled for this collection")]
+    RenewalDisabled {},
+
+    #[error("Payment must cover at least one renewal period of {price}")]
+    InsufficientPayment { price: String },
+
+    #[error("CW20 token {address} is not accepted as payment")]
+    Cw20NotAccepted { address: String },
+
+    #[error("Paid minting is not enabled for this token")]
+    PaidMintDisabled {},
+
+    #[error("Only the minter can set {field}")]
+    MinterOnlyMetadata { field: String },
+}
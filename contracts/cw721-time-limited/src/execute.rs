@@ -1,11 +1,141 @@
-use crate::state::{Cw721TimeLimited, Extension};
-use cosmwasm_std::{Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response};
-use cw721::{Cw721ReceiveMsg, Expiration};
-use cw721_base::{
-    state::{Approval, TokenInfo},
-    ContractError,
+use crate::error::ContractError;
+use crate::msg::ReceiveMsg;
+use crate::state::{
+    expired_token_ids, index_expiration, token_status, unindex_expiration, Config,
+    Cw721TimeLimited, Extension, FrozenClock, LifetimeConfig, PendingRoyalty, PendingTransfers,
+    PostExpiryPolicy, Redemption, RoyaltyRecipient, TokenStatus, Transferability, BOUNTY_POOL,
+    CONFIG, CONSUMERS, CREATOR, DEFAULT_ROYALTY_TIMELOCK, INDEX_BACKFILL, MAX_ROYALTY_BPS,
+    SCANNERS,
+};
+use cosmwasm_std::{
+    coins, from_binary, to_binary, Addr, Api, BankMsg, Binary, BlockInfo, CosmosMsg, Deps, DepsMut,
+    Empty, Env, Event, MessageInfo, Order, Response, StdError, StdResult, Timestamp, Uint128,
+    Uint64, WasmMsg,
 };
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw721::{Cw721ReceiveMsg, Expiration};
+use cw721_base::state::{Approval, TokenInfo};
 use cw_ownable::OwnershipError;
+use cw_storage_plus::Bound;
+use cw_utils::{must_pay, Duration, Scheduled};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+
+/// stores a new token, its royalties are not stored with it but taken from the config.
+/// If a lifetime is given, the token expires that long after the current block,
+/// tokens minted without any expiration get the default lifetime of the collection
+pub fn _mint(
+    deps: DepsMut,
+    env: &Env,
+    token_id: &str,
+    owner: &str,
+    token_uri: Option<String>,
+    extension: Extension,
+    lifetime: Option<Duration>,
+) -> Result<TokenInfo<Extension>, ContractError> {
+    let mut extension = extension.unwrap_or_default();
+    let config = CONFIG.load(deps.storage)?;
+
+    // a lifetime is converted to an absolute expiration against the current block
+    let pending_lifetime = extension.pending_lifetime;
+    let set = [
+        extension.expires.is_some(),
+        lifetime.is_some(),
+        pending_lifetime.is_some(),
+    ];
+    if set.into_iter().filter(|is_set| *is_set).count() > 1 {
+        return Err(ContractError::ConflictingExpiration {});
+    }
+    if let Some(lifetime) = lifetime.or(match (extension.expires, pending_lifetime) {
+        (None, None) => config.lifetime.default_lifetime,
+        _ => None,
+    }) {
+        extension.expires = Some(lifetime.after(&env.block));
+    }
+
+    // the expiration must be of an allowed kind, still ahead and within the lifetime bounds,
+    // a pending lifetime is checked as if the token was activated right away
+    let expires = match pending_lifetime {
+        Some(pending_lifetime) => pending_lifetime.after(&env.block),
+        None => extension.expires.unwrap_or_default(),
+    };
+    if !config.allowed_expiration.allows(&expires) {
+        return Err(ContractError::ExpirationKindNotAllowed {
+            expires: expires.to_string(),
+        });
+    }
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::AlreadyExpired {});
+    }
+    check_lifetime(&config.lifetime, &env.block, &expires)?;
+
+    // the validity window must not be empty
+    let starts_too_late = match (extension.valid_from, expires) {
+        (Some(Scheduled::AtHeight(start)), Expiration::AtHeight(end)) => start >= end,
+        (Some(Scheduled::AtTime(start)), Expiration::AtTime(end)) => start >= end,
+        _ => false,
+    };
+    if starts_too_late {
+        return Err(ContractError::InvalidValidityWindow {});
+    }
+
+    // every use is available at mint
+    if extension.max_uses == Some(0) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Max uses must be greater than zero",
+        )));
+    }
+    extension.uses_remaining = extension.max_uses;
+
+    // only scanners can redeem a token, once it exists
+    if extension.redemption.is_some() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Cannot redeem a token in mint message",
+        )));
+    }
+
+    // only FreezeExpiry can pause the expiry clock
+    if extension.frozen.is_some() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Cannot freeze the expiry clock in mint message",
+        )));
+    }
+
+    // return error if royalty is set
+    if extension.royalty_percentage.is_some() || extension.royalty_payment_address.is_some() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Cannot set royalty information in mint message",
+        )));
+    }
+
+    // the minter can split the royalties of this token differently,
+    // paid mints cannot get this far with a split
+    if let Some(recipients) = &extension.royalty_recipients {
+        validate_royalty_recipients(deps.api, recipients)?;
+    }
+
+    // create the token
+    let token = TokenInfo {
+        owner: deps.api.addr_validate(owner)?,
+        approvals: vec![],
+        token_uri,
+        extension: Some(extension),
+    };
+    let contract = Cw721TimeLimited::default();
+    contract
+        .tokens
+        .update(deps.storage, token_id, |old| match old {
+            Some(_) => Err(ContractError::Claimed {}),
+            None => Ok(token.clone()),
+        })?;
+    contract.increment_tokens(deps.storage)?;
+    index_expiration(deps.storage, token_id, &token)?;
+
+    Ok(token)
+}
 
 pub fn _transfer_nft(
     deps: DepsMut,
@@ -17,14 +147,16 @@ pub fn _transfer_nft(
     let mut token = Cw721TimeLimited::default()
         .tokens
         .load(deps.storage, token_id)?;
+    let recipient = deps.api.addr_validate(recipient)?;
     // ensure we have permissions
-    check_can_send(deps.as_ref(), env, info, &token)?;
+    check_can_send(deps.as_ref(), env, info, &token, Some(&recipient))?;
     // set owner and remove existing approvals
-    token.owner = deps.api.addr_validate(recipient)?;
+    token.owner = recipient;
     token.approvals = vec![];
     Cw721TimeLimited::default()
         .tokens
         .save(deps.storage, token_id, &token)?;
+    index_expiration(deps.storage, token_id, &token)?;
     Ok(token)
 }
 
@@ -77,23 +209,32 @@ pub fn check_can_approve(
     info: &MessageInfo,
     token: &TokenInfo<Extension>,
 ) -> Result<(), ContractError> {
-    // if the token is expired, just minter can send
-    match &token.extension {
-        Some(metadata) => match metadata.expires {
-            Some(expires) => {
-                if expires.is_expired(&env.block) {
-                    cw_ownable::assert_owner(deps.storage, &info.sender)?;
-                } else if token.owner == info.sender {
-                    return Ok(());
-                }
-            }
-            None => {
-                if token.owner == info.sender {
-                    return Ok(());
-                }
-            }
+    let config = CONFIG.load(deps.storage)?;
+    let status = token_status(&config, token, &env.block);
+    // a token bound to its owner cannot be approved
+    if Transferability::of(&config, token) != Transferability::Always {
+        return Err(ContractError::NonTransferable {});
+    }
+    match status {
+        // if the token is expired or used up, just minter can approve,
+        // and only if the policy lets the minter move it
+        TokenStatus::Expired | TokenStatus::UsedUp => match config.post_expiry {
+            PostExpiryPolicy::Reclaim => cw_ownable::assert_owner(deps.storage, &info.sender)?,
+            _ => return Err(ContractError::PostExpiryRestricted {}),
         },
-        None => {
+        // nobody can approve during the grace period
+        TokenStatus::Grace => return Err(ContractError::TokenInGracePeriod {}),
+        // unless allowed, nobody can approve before the token can be used
+        TokenStatus::PendingActivation if config.pending_transfers != PendingTransfers::Allow => {
+            return Err(ContractError::TokenNotActivated {})
+        }
+        TokenStatus::NotYetValid if config.not_yet_valid_transfers != PendingTransfers::Allow => {
+            return Err(ContractError::TokenNotYetValid {})
+        }
+        TokenStatus::Active
+        | TokenStatus::NeverExpires
+        | TokenStatus::PendingActivation
+        | TokenStatus::NotYetValid => {
             if token.owner == info.sender {
                 return Ok(());
             }
@@ -116,34 +257,95 @@ pub fn check_can_approve(
     }
 }
 
-/// returns true iff the sender can transfer ownership of the token
+/// returns true iff the sender can burn the token, like sending it except that
+/// the minter can burn expired tokens when the policy is to burn them
+pub fn check_can_burn(
+    deps: Deps,
+    env: &Env,
+    info: &MessageInfo,
+    token: &TokenInfo<Extension>,
+) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let status = token_status(&config, token, &env.block);
+    if config.post_expiry == PostExpiryPolicy::Burn
+        && matches!(status, TokenStatus::Expired | TokenStatus::UsedUp)
+    {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+        return Ok(());
+    }
+    check_can_send(deps, env, info, token, None)
+}
+
+/// returns true iff the sender can transfer ownership of the token to `recipient`,
+/// which is None when the token is burned
 pub fn check_can_send(
     deps: Deps,
     env: &Env,
     info: &MessageInfo,
     token: &TokenInfo<Extension>,
+    recipient: Option<&Addr>,
 ) -> Result<(), ContractError> {
-    // if the token is expired, just minter can send
-    match &token.extension {
-        Some(metadata) => match metadata.expires {
-            Some(expires) => {
-                if expires.is_expired(&env.block) {
-                    if cw_ownable::assert_owner(deps.storage, &info.sender).is_ok() {
-                        return Ok(());
-                    } else {
-                        return Err(ContractError::Ownership(OwnershipError::NotOwner));
-                    }
-                } else if token.owner == info.sender {
-                    return Ok(());
-                }
+    let config = CONFIG.load(deps.storage)?;
+    let status = token_status(&config, token, &env.block);
+    let expired = matches!(status, TokenStatus::Expired | TokenStatus::UsedUp);
+    match Transferability::of(&config, token) {
+        Transferability::Always => {}
+        // once expired, the holder can hand the token back to the minter or the vault
+        Transferability::ExpiredToMinter if expired && token.owner == info.sender => {
+            let returned = match recipient {
+                Some(recipient) => _is_minter_or_vault(deps, &config, recipient)?,
+                None => false,
+            };
+            if returned {
+                return Ok(());
             }
-            None => {
-                if token.owner == info.sender {
-                    return Ok(());
-                }
+        }
+        // once expired, the post-expiry policy decides who can move the token
+        _ if expired => {}
+        // the minter can still revoke a token bound to its owner
+        Transferability::Never | Transferability::ExpiredToMinter => {
+            if cw_ownable::assert_owner(deps.storage, &info.sender).is_ok() {
+                return Ok(());
+            } else {
+                return Err(ContractError::NonTransferable {});
             }
-        },
-        None => {
+        }
+    }
+    match status {
+        // if the token is expired or used up, just minter can send,
+        // and only if the policy lets the minter move it
+        TokenStatus::Expired | TokenStatus::UsedUp => {
+            if config.post_expiry != PostExpiryPolicy::Reclaim {
+                return Err(ContractError::PostExpiryRestricted {});
+            }
+            if cw_ownable::assert_owner(deps.storage, &info.sender).is_ok() {
+                return Ok(());
+            } else {
+                return Err(ContractError::Ownership(OwnershipError::NotOwner));
+            }
+        }
+        // nobody can send during the grace period
+        TokenStatus::Grace => return Err(ContractError::TokenInGracePeriod {}),
+        // unless allowed, just minter can send before activation
+        TokenStatus::PendingActivation if config.pending_transfers != PendingTransfers::Allow => {
+            if cw_ownable::assert_owner(deps.storage, &info.sender).is_ok() {
+                return Ok(());
+            } else {
+                return Err(ContractError::TokenNotActivated {});
+            }
+        }
+        // unless allowed, just minter can send before the validity window starts
+        TokenStatus::NotYetValid if config.not_yet_valid_transfers != PendingTransfers::Allow => {
+            if cw_ownable::assert_owner(deps.storage, &info.sender).is_ok() {
+                return Ok(());
+            } else {
+                return Err(ContractError::TokenNotYetValid {});
+            }
+        }
+        TokenStatus::Active
+        | TokenStatus::NeverExpires
+        | TokenStatus::PendingActivation
+        | TokenStatus::NotYetValid => {
             if token.owner == info.sender {
                 return Ok(());
             }
@@ -252,6 +454,30 @@ pub fn revoke_all(
         .add_attribute("operator", operator))
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn mint(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    owner: String,
+    token_uri: Option<String>,
+    extension: Extension,
+    lifetime: Option<Duration>,
+) -> Result<Response<Empty>, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    _mint(
+        deps, &env, &token_id, &owner, token_uri, extension, lifetime,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mint")
+        .add_attribute("minter", info.sender)
+        .add_attribute("owner", owner)
+        .add_attribute("token_id", token_id))
+}
+
 pub fn transfer_nft(
     deps: DepsMut,
     env: Env,
@@ -294,6 +520,21 @@ pub fn send_nft(
         .add_attribute("token_id", token_id))
 }
 
+/// removes a token and returns the `burn` event describing it
+fn _burn(
+    deps: DepsMut,
+    token_id: &str,
+    token: &TokenInfo<Extension>,
+) -> Result<Event, ContractError> {
+    Cw721TimeLimited::default()
+        .tokens
+        .remove(deps.storage, token_id)?;
+    Cw721TimeLimited::default().decrement_tokens(deps.storage)?;
+    unindex_expiration(deps.storage, token_id, token);
+
+    Ok(Event::new("burn").add_attribute("token_id", token_id))
+}
+
 pub fn burn(
     deps: DepsMut,
     env: Env,
@@ -303,15 +544,1084 @@ pub fn burn(
     let token = Cw721TimeLimited::default()
         .tokens
         .load(deps.storage, &token_id)?;
-    check_can_send(deps.as_ref(), &env, &info, &token)?;
+    check_can_burn(deps.as_ref(), &env, &info, &token)?;
+
+    _burn(deps, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "burn")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id))
+}
+
+/// returns an error unless `new` is of the same kind as `old` and strictly later than it
+pub fn validate_extension(old: &Expiration, new: &Expiration) -> Result<(), ContractError> {
+    match (old, new) {
+        (Expiration::AtHeight(_), Expiration::AtHeight(_))
+        | (Expiration::AtTime(_), Expiration::AtTime(_)) => {
+            if new <= old {
+                return Err(ContractError::ExpirationNotExtended {});
+            }
+            Ok(())
+        }
+        _ => Err(ContractError::ExpirationKindMismatch {}),
+    }
+}
+
+/// starts the pending lifetime of a token, can only be called by its owner
+pub fn activate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    let contract = Cw721TimeLimited::default();
+    let mut token = contract.tokens.load(deps.storage, &token_id)?;
+    if token.owner != info.sender {
+        return Err(ContractError::Ownership(OwnershipError::NotOwner));
+    }
+
+    let metadata = token.extension.get_or_insert_with(Default::default);
+    let lifetime =
+        metadata
+            .pending_lifetime
+            .take()
+            .ok_or_else(|| ContractError::NotPendingActivation {
+                token_id: token_id.clone(),
+            })?;
+    let expires = lifetime.after(&env.block);
+    metadata.expires = Some(expires);
+
+    // the bounds may have changed since the token was minted
+    let config = CONFIG.load(deps.storage)?;
+    check_lifetime(&config.lifetime, &env.block, &expires)?;
+
+    contract.tokens.save(deps.storage, &token_id, &token)?;
+    index_expiration(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "activate")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("expires", expires.to_string()))
+}
+
+/// returns an error if `expires` falls outside the lifetime bounds counted from `block`
+pub fn check_lifetime(
+    lifetime: &LifetimeConfig,
+    block: &BlockInfo,
+    expires: &Expiration,
+) -> Result<(), ContractError> {
+    if let Some(min) = lifetime.min_lifetime {
+        match expires.partial_cmp(&min.after(block)) {
+            Some(Ordering::Less) => {
+                return Err(ContractError::LifetimeTooShort {
+                    min: min.to_string(),
+                })
+            }
+            None => {
+                return Err(ContractError::LifetimeKindMismatch {
+                    bound: min.to_string(),
+                })
+            }
+            _ => {}
+        }
+    }
+    if let Some(max) = lifetime.max_lifetime {
+        match expires.partial_cmp(&max.after(block)) {
+            Some(Ordering::Greater) => {
+                return Err(ContractError::LifetimeTooLong {
+                    max: max.to_string(),
+                })
+            }
+            None => {
+                return Err(ContractError::LifetimeKindMismatch {
+                    bound: max.to_string(),
+                })
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// returns an error unless the split has distinct valid addresses
+/// and its shares add up to the whole royalty
+pub fn validate_royalty_recipients(
+    api: &dyn Api,
+    recipients: &[RoyaltyRecipient],
+) -> Result<(), ContractError> {
+    let mut seen = HashSet::new();
+    for recipient in recipients {
+        api.addr_validate(&recipient.address)?;
+        if recipient.share_bps == 0 || !seen.insert(recipient.address.as_str()) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Royalty recipients need a share and can only be listed once",
+            )));
+        }
+    }
+    let total = recipients.iter().map(|recipient| recipient.share_bps).sum();
+    if total != MAX_ROYALTY_BPS {
+        return Err(ContractError::RoyaltySharesMismatch { total });
+    }
+    Ok(())
+}
+
+/// returns an error unless the bounds and the default lifetime are of one kind
+/// and the default lies within the bounds
+pub fn validate_lifetime_config(lifetime: &LifetimeConfig) -> Result<(), ContractError> {
+    let durations = [
+        lifetime.min_lifetime,
+        lifetime.default_lifetime,
+        lifetime.max_lifetime,
+    ];
+    let mut previous: Option<Duration> = None;
+    for duration in durations.into_iter().flatten() {
+        let ordered = match (previous, duration) {
+            (None, _) => true,
+            (Some(Duration::Height(a)), Duration::Height(b))
+            | (Some(Duration::Time(a)), Duration::Time(b)) => a <= b,
+            _ => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Lifetime settings must all be in blocks or all in seconds",
+                )))
+            }
+        };
+        if !ordered {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Lifetime settings must satisfy min <= default <= max",
+            )));
+        }
+        previous = Some(duration);
+    }
+    Ok(())
+}
+
+pub fn update_lifetime_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    lifetime: LifetimeConfig,
+) -> Result<Response<Empty>, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+    validate_lifetime_config(&lifetime)?;
+
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config.lifetime = lifetime;
+        Ok(config)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_lifetime_config")
+        .add_attribute("sender", info.sender))
+}
 
+/// returns the current expiration of a token, or an error if it never expires
+fn _current_expiration(
+    token: &TokenInfo<Extension>,
+    token_id: &str,
+) -> Result<Expiration, ContractError> {
+    match token
+        .extension
+        .as_ref()
+        .and_then(|metadata| metadata.expires)
+    {
+        Some(Expiration::Never {}) | None => Err(ContractError::NoExpiration {
+            token_id: token_id.to_string(),
+        }),
+        Some(expires) => Ok(expires),
+    }
+}
+
+/// stores the new expiration of a token and returns the `renew` event describing the change,
+/// the new expiration must respect the lifetime bounds
+fn _set_expiration(
+    deps: DepsMut,
+    block: &BlockInfo,
+    token_id: &str,
+    mut token: TokenInfo<Extension>,
+    old_expires: Expiration,
+    new_expires: Expiration,
+) -> Result<Event, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    check_lifetime(&config.lifetime, block, &new_expires)?;
+
+    unindex_expiration(deps.storage, token_id, &token);
+    token.extension.get_or_insert_with(Default::default).expires = Some(new_expires);
     Cw721TimeLimited::default()
         .tokens
-        .remove(deps.storage, &token_id)?;
-    Cw721TimeLimited::default().decrement_tokens(deps.storage)?;
+        .save(deps.storage, token_id, &token)?;
+    index_expiration(deps.storage, token_id, &token)?;
+
+    Ok(Event::new("renew")
+        .add_attribute("token_id", token_id)
+        .add_attribute("old_expires", old_expires.to_string())
+        .add_attribute("new_expires", new_expires.to_string()))
+}
+
+pub fn extend_expiration(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    expires: Expiration,
+) -> Result<Response<Empty>, ContractError> {
+    // only minter can extend the expiration of a token
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    let token = Cw721TimeLimited::default()
+        .tokens
+        .load(deps.storage, &token_id)?;
+    let old_expires = _current_expiration(&token, &token_id)?;
+    validate_extension(&old_expires, &expires)?;
+
+    let event = _set_expiration(deps, &env.block, &token_id, token, old_expires, expires)?;
 
     Ok(Response::new()
-        .add_attribute("action", "burn")
+        .add_attribute("action", "extend_expiration")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_event(event))
+}
+
+/// pauses the expiry clock of a token, it stays active until unfrozen.
+/// Can only be called by the minter on tokens that are not expired
+pub fn freeze_expiry(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    let contract = Cw721TimeLimited::default();
+    let mut token = contract.tokens.load(deps.storage, &token_id)?;
+    let expires = _current_expiration(&token, &token_id)?;
+    if token
+        .extension
+        .as_ref()
+        .map_or(false, |metadata| metadata.frozen.is_some())
+    {
+        return Err(ContractError::AlreadyFrozen { token_id });
+    }
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::TokenExpired { token_id });
+    }
+
+    // frozen tokens are left out of the expiration index
+    unindex_expiration(deps.storage, &token_id, &token);
+    let frozen = FrozenClock {
+        height: env.block.height,
+        time: env.block.time,
+    };
+    token.extension.get_or_insert_with(Default::default).frozen = Some(frozen.clone());
+    contract.tokens.save(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "freeze_expiry")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", &token_id)
+        .add_event(
+            Event::new("freeze_expiry")
+                .add_attribute("token_id", token_id)
+                .add_attribute("expires", expires.to_string())
+                .add_attribute("frozen_at_height", frozen.height.to_string())
+                .add_attribute("frozen_at_time", frozen.time.to_string()),
+        ))
+}
+
+/// resumes the expiry clock of a token, its expiration is pushed forward
+/// by the time it was frozen. Can only be called by the minter
+pub fn unfreeze_expiry(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    let contract = Cw721TimeLimited::default();
+    let mut token = contract.tokens.load(deps.storage, &token_id)?;
+    let old_expires = _current_expiration(&token, &token_id)?;
+    let metadata = token.extension.get_or_insert_with(Default::default);
+    let frozen = metadata
+        .frozen
+        .take()
+        .ok_or_else(|| ContractError::NotFrozen {
+            token_id: token_id.clone(),
+        })?;
+
+    // credit the frozen time back to the token
+    let credit =
+        frozen
+            .credit(&env.block, &old_expires)
+            .ok_or_else(|| ContractError::NoExpiration {
+                token_id: token_id.clone(),
+            })?;
+    let new_expires = (old_expires + credit)?;
+    metadata.expires = Some(new_expires);
+    contract.tokens.save(deps.storage, &token_id, &token)?;
+    index_expiration(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "unfreeze_expiry")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", &token_id)
+        .add_event(
+            Event::new("unfreeze_expiry")
+                .add_attribute("token_id", token_id)
+                .add_attribute("old_expires", old_expires.to_string())
+                .add_attribute("new_expires", new_expires.to_string())
+                .add_attribute("frozen_for", credit.to_string()),
+        ))
+}
+
+/// returns an error unless the token can be used at the current block
+fn _assert_usable(
+    deps: Deps,
+    env: &Env,
+    token_id: &str,
+    token: &TokenInfo<Extension>,
+) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    match token_status(&config, token, &env.block) {
+        TokenStatus::Active | TokenStatus::NeverExpires => Ok(()),
+        TokenStatus::Expired | TokenStatus::Grace | TokenStatus::UsedUp => {
+            Err(ContractError::TokenExpired {
+                token_id: token_id.to_string(),
+            })
+        }
+        TokenStatus::PendingActivation | TokenStatus::NotYetValid => {
+            Err(ContractError::TokenNotUsable {})
+        }
+    }
+}
+
+/// uses up `amount` uses of a token, can be called by its owner or an authorized consumer
+pub fn consume(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    amount: u32,
+) -> Result<Response<Empty>, ContractError> {
+    let contract = Cw721TimeLimited::default();
+    let mut token = contract.tokens.load(deps.storage, &token_id)?;
+    if token.owner != info.sender && !CONSUMERS.has(deps.storage, &info.sender) {
+        return Err(ContractError::Ownership(OwnershipError::NotOwner));
+    }
+    if amount == 0 {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Amount must be greater than zero",
+        )));
+    }
+
+    // only a token that can be used at this block can be consumed
+    _assert_usable(deps.as_ref(), &env, &token_id, &token)?;
+
+    let metadata = token.extension.get_or_insert_with(Default::default);
+    let uses_remaining = metadata
+        .uses_remaining
+        .ok_or_else(|| ContractError::NoUsageLimit {
+            token_id: token_id.clone(),
+        })?;
+    if uses_remaining < amount {
+        return Err(ContractError::NotEnoughUses { uses_remaining });
+    }
+    metadata.uses_remaining = Some(uses_remaining - amount);
+    contract.tokens.save(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "consume")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", &token_id)
+        .add_event(
+            Event::new("consume")
+                .add_attribute("token_id", token_id)
+                .add_attribute("amount", amount.to_string())
+                .add_attribute("uses_remaining", (uses_remaining - amount).to_string()),
+        ))
+}
+
+/// adds and removes the addresses allowed to consume uses of any token,
+/// can only be called by the minter
+pub fn update_consumers(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response<Empty>, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    for consumer in add {
+        let consumer = deps.api.addr_validate(&consumer)?;
+        CONSUMERS.save(deps.storage, &consumer, &Empty {})?;
+    }
+    for consumer in remove {
+        let consumer = deps.api.addr_validate(&consumer)?;
+        CONSUMERS.remove(deps.storage, &consumer);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_consumers")
+        .add_attribute("sender", info.sender))
+}
+
+/// marks a token as redeemed, can only be called by a scanner and only once per token
+pub fn redeem(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    if !SCANNERS.has(deps.storage, &info.sender) {
+        return Err(ContractError::NotScanner {});
+    }
+
+    let contract = Cw721TimeLimited::default();
+    let mut token = contract.tokens.load(deps.storage, &token_id)?;
+    _assert_usable(deps.as_ref(), &env, &token_id, &token)?;
+
+    let metadata = token.extension.get_or_insert_with(Default::default);
+    if metadata.redemption.is_some() {
+        return Err(ContractError::AlreadyRedeemed { token_id });
+    }
+    metadata.redemption = Some(Redemption {
+        scanner: info.sender.clone(),
+        height: env.block.height,
+        time: env.block.time,
+    });
+    contract.tokens.save(deps.storage, &token_id, &token)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "redeem")
         .add_attribute("sender", info.sender)
         .add_attribute("token_id", token_id))
 }
+
+/// adds and removes the addresses allowed to redeem tokens,
+/// can only be called by the minter
+pub fn update_scanners(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response<Empty>, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    for scanner in add {
+        let scanner = deps.api.addr_validate(&scanner)?;
+        SCANNERS.save(deps.storage, &scanner, &Empty {})?;
+    }
+    for scanner in remove {
+        let scanner = deps.api.addr_validate(&scanner)?;
+        SCANNERS.remove(deps.storage, &scanner);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_scanners")
+        .add_attribute("sender", info.sender))
+}
+
+/// returns an error unless the sender is the creator or the minter
+fn _assert_creator_or_minter(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    if CREATOR.load(deps.storage)?.as_deref() == Some(sender.as_str())
+        || cw_ownable::assert_owner(deps.storage, sender).is_ok()
+    {
+        return Ok(());
+    }
+    Err(ContractError::NotCreatorOrMinter {})
+}
+
+pub fn update_royalty_config(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    royalty_bps: Option<u64>,
+    royalty_payment_address: Option<String>,
+    royalty_recipients: Option<Vec<RoyaltyRecipient>>,
+) -> Result<Response<Empty>, ContractError> {
+    _assert_creator_or_minter(deps.as_ref(), &info.sender)?;
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let mut res = Response::new()
+        .add_attribute("action", "update_royalty_config")
+        .add_attribute("sender", info.sender);
+
+    // a new payment address takes effect immediately
+    if let Some(royalty_payment_address) = royalty_payment_address {
+        deps.api.addr_validate(&royalty_payment_address)?;
+        res = res.add_attribute("royalty_payment_address", &royalty_payment_address);
+        config.royalty_payment_address = Some(royalty_payment_address);
+    }
+
+    // so does a new split, an empty one pays everything to the payment address
+    if let Some(royalty_recipients) = royalty_recipients {
+        if !royalty_recipients.is_empty() {
+            validate_royalty_recipients(deps.api, &royalty_recipients)?;
+        }
+        res = res.add_attribute("royalty_recipients", royalty_recipients.len().to_string());
+        config.royalty_recipients = royalty_recipients;
+    }
+
+    // a new rate is only proposed, replacing any earlier proposal
+    if let Some(royalty_bps) = royalty_bps {
+        let max = config
+            .max_royalty_bps
+            .unwrap_or(config.royalty_bps.unwrap_or_default());
+        if royalty_bps > max {
+            return Err(ContractError::RoyaltyAboveMax { max });
+        }
+        let applicable_at = config
+            .royalty_timelock
+            .unwrap_or(DEFAULT_ROYALTY_TIMELOCK)
+            .after(&env.block);
+        res = res
+            .add_attribute("proposed_royalty_bps", royalty_bps.to_string())
+            .add_attribute("applicable_at", applicable_at.to_string());
+        config.pending_royalty = Some(PendingRoyalty {
+            royalty_bps,
+            applicable_at,
+        });
+    }
+
+    CONFIG.save(deps.storage, &config)?;
+    Ok(res)
+}
+
+pub fn apply_royalty_config(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<Empty>, ContractError> {
+    _assert_creator_or_minter(deps.as_ref(), &info.sender)?;
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let pending = config
+        .pending_royalty
+        .take()
+        .ok_or(ContractError::NoPendingRoyalty {})?;
+    if !pending.applicable_at.is_expired(&env.block) {
+        return Err(ContractError::RoyaltyTimelocked {
+            applicable_at: pending.applicable_at.to_string(),
+        });
+    }
+    config.royalty_bps = Some(pending.royalty_bps);
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "apply_royalty_config")
+        .add_attribute("sender", info.sender)
+        .add_attribute("royalty_bps", pending.royalty_bps.to_string()))
+}
+
+/// returns the address receiving payments, the configured treasury or else the minter
+fn _treasury(deps: Deps, config: &Config) -> Result<Addr, ContractError> {
+    match &config.treasury {
+        Some(treasury) => Ok(treasury.clone()),
+        None => cw_ownable::get_ownership(deps.storage)?
+            .owner
+            .ok_or(ContractError::Ownership(OwnershipError::NoOwner)),
+    }
+}
+
+/// extends the token of `owner` by every whole renewal period covered by `paid`,
+/// returns the `renew` event, the number of periods bought and their cost
+#[allow(clippy::too_many_arguments)]
+fn _paid_renewal(
+    deps: DepsMut,
+    env: &Env,
+    owner: &Addr,
+    token_id: &str,
+    paid: Uint128,
+    unit_price: Uint128,
+    period: Duration,
+    price_label: String,
+) -> Result<(Event, u64, Uint128), ContractError> {
+    let token = Cw721TimeLimited::default()
+        .tokens
+        .load(deps.storage, token_id)?;
+    // only the owner can renew, and only until the grace period is over
+    if token.owner != *owner {
+        return Err(ContractError::Ownership(OwnershipError::NotOwner));
+    }
+    let old_expires = _current_expiration(&token, token_id)?;
+    let config = CONFIG.load(deps.storage)?;
+    if token_status(&config, &token, &env.block) == TokenStatus::Expired {
+        return Err(ContractError::TokenExpired {
+            token_id: token_id.to_string(),
+        });
+    }
+
+    // extend by every whole period paid for, a payment too large to count is rejected
+    let periods = Uint64::try_from(paid / unit_price).map_err(StdError::from)?;
+    if periods.is_zero() {
+        return Err(ContractError::InsufficientPayment { price: price_label });
+    }
+    let new_expires = _extend_by_periods(old_expires, period, periods)?;
+
+    let event = _set_expiration(deps, &env.block, token_id, token, old_expires, new_expires)?;
+    Ok((event, periods.u64(), unit_price * Uint128::from(periods)))
+}
+
+/// returns `expires` pushed forward by `periods` times `period`,
+/// or an error if that overflows
+fn _extend_by_periods(
+    expires: Expiration,
+    period: Duration,
+    periods: Uint64,
+) -> Result<Expiration, ContractError> {
+    let new_expires = match (expires, period) {
+        (Expiration::AtHeight(height), Duration::Height(blocks)) => Expiration::AtHeight(
+            Uint64::new(blocks)
+                .checked_mul(periods)
+                .and_then(|blocks| blocks.checked_add(Uint64::new(height)))
+                .map_err(StdError::from)?
+                .u64(),
+        ),
+        (Expiration::AtTime(time), Duration::Time(seconds)) => {
+            Expiration::AtTime(Timestamp::from_nanos(
+                Uint64::new(seconds)
+                    .checked_mul(periods)
+                    .and_then(|seconds| seconds.checked_mul(Uint64::new(1_000_000_000)))
+                    .and_then(|nanos| nanos.checked_add(Uint64::new(time.nanos())))
+                    .map_err(StdError::from)?
+                    .u64(),
+            ))
+        }
+        _ => return Err(ContractError::ExpirationKindMismatch {}),
+    };
+    Ok(new_expires)
+}
+
+pub fn renew(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let (price, period) = match (&config.renewal_price, config.renewal_period) {
+        (Some(price), Some(period)) => (price, period),
+        _ => return Err(ContractError::RenewalDisabled {}),
+    };
+
+    let paid = must_pay(&info, &price.denom)?;
+    let (event, periods, cost) = _paid_renewal(
+        deps.branch(),
+        &env,
+        &info.sender,
+        &token_id,
+        paid,
+        price.amount,
+        period,
+        price.to_string(),
+    )?;
+
+    // forward the cost of the periods to the treasury and refund the rest
+    let treasury = _treasury(deps.as_ref(), &config)?;
+    let mut res = Response::new()
+        .add_message(BankMsg::Send {
+            to_address: treasury.to_string(),
+            amount: coins(cost.u128(), &price.denom),
+        })
+        .add_attribute("action", "renew")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("token_id", token_id)
+        .add_attribute("periods", periods.to_string())
+        .add_event(event);
+    if paid > cost {
+        res = res.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: coins((paid - cost).u128(), &price.denom),
+        });
+    }
+
+    Ok(res)
+}
+
+/// builds the message moving `amount` of the CW20 token at `cw20_addr` to `recipient`
+fn _cw20_transfer(
+    cw20_addr: &Addr,
+    recipient: &Addr,
+    amount: Uint128,
+) -> Result<CosmosMsg, ContractError> {
+    Ok(WasmMsg::Execute {
+        contract_addr: cw20_addr.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: recipient.to_string(),
+            amount,
+        })?,
+        funds: vec![],
+    }
+    .into())
+}
+
+/// handles CW20 tokens sent to this contract as payment for minting or renewing
+pub fn receive(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response<Empty>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    // info.sender is the CW20 contract, only allowlisted ones are accepted
+    let cw20_addr = info.sender;
+    let price = config
+        .cw20_prices
+        .iter()
+        .find(|price| price.address == cw20_addr)
+        .ok_or_else(|| ContractError::Cw20NotAccepted {
+            address: cw20_addr.to_string(),
+        })?;
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    let paid = wrapper.amount;
+    let treasury = _treasury(deps.as_ref(), &config)?;
+
+    let (res, cost) = match from_binary(&wrapper.msg)? {
+        ReceiveMsg::Mint {
+            token_id,
+            token_uri,
+            extension,
+        } => {
+            let (mint_amount, period) = match (price.mint_amount, config.renewal_period) {
+                (Some(mint_amount), Some(period)) => (mint_amount, period),
+                _ => return Err(ContractError::PaidMintDisabled {}),
+            };
+            if paid < mint_amount {
+                return Err(ContractError::InsufficientPayment {
+                    price: format!("{}{}", mint_amount, cw20_addr),
+                });
+            }
+
+            // a paid mint always lasts one renewal period from now
+            _assert_buyer_metadata(&extension)?;
+            _mint(
+                deps.branch(),
+                &env,
+                &token_id,
+                sender.as_str(),
+                token_uri,
+                extension,
+                Some(period),
+            )?;
+
+            let res = Response::new()
+                .add_attribute("action", "mint")
+                .add_attribute("minter", sender.clone())
+                .add_attribute("owner", sender.clone())
+                .add_attribute("token_id", token_id);
+            (res, mint_amount)
+        }
+        ReceiveMsg::Renew { token_id } => {
+            let (renewal_amount, period) = match (price.renewal_amount, config.renewal_period) {
+                (Some(renewal_amount), Some(period)) => (renewal_amount, period),
+                _ => return Err(ContractError::RenewalDisabled {}),
+            };
+            let (event, periods, cost) = _paid_renewal(
+                deps.branch(),
+                &env,
+                &sender,
+                &token_id,
+                paid,
+                renewal_amount,
+                period,
+                format!("{}{}", renewal_amount, cw20_addr),
+            )?;
+
+            let res = Response::new()
+                .add_attribute("action", "renew")
+                .add_attribute("sender", sender.clone())
+                .add_attribute("token_id", token_id)
+                .add_attribute("periods", periods.to_string())
+                .add_event(event);
+            (res, cost)
+        }
+    };
+
+    // forward the cost to the treasury and refund the rest
+    let mut res = res.add_message(_cw20_transfer(&cw20_addr, &treasury, cost)?);
+    if paid > cost {
+        res = res.add_message(_cw20_transfer(&cw20_addr, &sender, paid - cost)?);
+    }
+
+    Ok(res)
+}
+
+pub fn index_expirations(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response<Empty>, ContractError> {
+    let start_after = INDEX_BACKFILL.may_load(deps.storage)?.ok_or_else(|| {
+        ContractError::Std(StdError::generic_err("Every token is already indexed"))
+    })?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let contract = Cw721TimeLimited::default();
+    let tokens = contract
+        .tokens
+        .range(
+            deps.storage,
+            start_after.as_deref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    // indexing a token again leaves the index as it is,
+    // expired tokens the policy was already applied to stay out of it
+    let config = CONFIG.load(deps.storage)?;
+    let recipient = _post_expiry_recipient(deps.as_ref(), &config)?;
+    for (token_id, token) in &tokens {
+        let handled = recipient.as_ref() == Some(&token.owner)
+            && token_status(&config, token, &env.block) == TokenStatus::Expired;
+        if !handled {
+            index_expiration(deps.storage, token_id, token)?;
+        }
+    }
+
+    let done = tokens.len() < limit;
+    if done {
+        INDEX_BACKFILL.remove(deps.storage);
+    } else {
+        INDEX_BACKFILL.save(
+            deps.storage,
+            &tokens.last().map(|(token_id, _)| token_id.clone()),
+        )?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "index_expirations")
+        .add_attribute("sender", info.sender)
+        .add_attribute("indexed", tokens.len().to_string())
+        .add_attribute("done", done.to_string()))
+}
+
+/// returns an error if the metadata of a paid mint sets what only the minter can
+fn _assert_buyer_metadata(extension: &Extension) -> Result<(), ContractError> {
+    let Some(extension) = extension else {
+        return Ok(());
+    };
+    let minter_only = [
+        ("max_uses", extension.max_uses.is_some()),
+        ("valid_from", extension.valid_from.is_some()),
+        ("transferability", extension.transferability.is_some()),
+        ("royalty_recipients", extension.royalty_recipients.is_some()),
+        ("expired_token_uri", extension.expired_token_uri.is_some()),
+        ("expired_image", extension.expired_image.is_some()),
+    ];
+    match minter_only.into_iter().find(|(_, is_set)| *is_set) {
+        Some((field, _)) => Err(ContractError::MinterOnlyMetadata {
+            field: field.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// returns true iff `address` is the minter or the reclaim vault
+fn _is_minter_or_vault(deps: Deps, config: &Config, address: &Addr) -> StdResult<bool> {
+    if config.reclaim_vault.as_ref() == Some(address) {
+        return Ok(true);
+    }
+    Ok(cw_ownable::get_ownership(deps.storage)?.owner.as_ref() == Some(address))
+}
+
+/// returns the address expired tokens go back to, the reclaim vault or else the minter
+fn _reclaim_recipient(deps: Deps, config: &Config) -> Result<Addr, ContractError> {
+    match &config.reclaim_vault {
+        Some(reclaim_vault) => Ok(reclaim_vault.clone()),
+        None => cw_ownable::get_ownership(deps.storage)?
+            .owner
+            .ok_or(ContractError::Ownership(OwnershipError::NoOwner)),
+    }
+}
+
+/// returns where expired tokens are moved under the post expiry policy,
+/// None if they are burned or kept by their holders
+fn _post_expiry_recipient(deps: Deps, config: &Config) -> Result<Option<Addr>, ContractError> {
+    match config.post_expiry {
+        PostExpiryPolicy::Reclaim => Ok(Some(_reclaim_recipient(deps, config)?)),
+        PostExpiryPolicy::RevertToCreator => {
+            let creator = CREATOR.load(deps.storage)?.ok_or_else(|| {
+                ContractError::Std(StdError::generic_err("Collection has no creator"))
+            })?;
+            Ok(Some(deps.api.addr_validate(&creator)?))
+        }
+        PostExpiryPolicy::Burn | PostExpiryPolicy::Freeze => Ok(None),
+    }
+}
+
+/// moves a token to `recipient`, clearing its approvals, and returns the `reclaim`
+/// event describing it. The policy is applied, so the token leaves the expiration index
+fn _reclaim(
+    deps: DepsMut,
+    token_id: &str,
+    mut token: TokenInfo<Extension>,
+    recipient: &Addr,
+) -> Result<Event, ContractError> {
+    unindex_expiration(deps.storage, token_id, &token);
+    let previous_owner = token.owner;
+    token.owner = recipient.clone();
+    token.approvals = vec![];
+    Cw721TimeLimited::default()
+        .tokens
+        .save(deps.storage, token_id, &token)?;
+
+    Ok(Event::new("reclaim")
+        .add_attribute("token_id", token_id)
+        .add_attribute("previous_owner", previous_owner)
+        .add_attribute("recipient", recipient))
+}
+
+pub fn reclaim_expired(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_ids: Option<Vec<String>>,
+    limit: Option<u32>,
+) -> Result<Response<Empty>, ContractError> {
+    // only minter can reclaim expired tokens
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    let mut res = Response::new()
+        .add_attribute("action", "reclaim_expired")
+        .add_attribute("sender", info.sender);
+    // expired tokens are kept by their holders
+    let config = CONFIG.load(deps.storage)?;
+    if config.post_expiry == PostExpiryPolicy::Freeze {
+        return Ok(res);
+    }
+    let recipient = _post_expiry_recipient(deps.as_ref(), &config)?;
+
+    let contract = Cw721TimeLimited::default();
+    let tokens = match token_ids {
+        Some(token_ids) => token_ids
+            .into_iter()
+            .map(|token_id| {
+                let token = contract.tokens.load(deps.storage, &token_id)?;
+                Ok((token_id, token))
+            })
+            .collect::<StdResult<Vec<_>>>()?,
+        // every scanned token leaves the index below, so the next call moves on
+        None => {
+            let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+            expired_token_ids(deps.storage, &config, &env.block)
+                .take(limit)
+                .collect::<StdResult<Vec<_>>>()?
+                .into_iter()
+                .map(|token_id| {
+                    let token = contract.tokens.load(deps.storage, &token_id)?;
+                    Ok((token_id, token))
+                })
+                .collect::<StdResult<Vec<_>>>()?
+        }
+    };
+
+    if let Some(recipient) = &recipient {
+        res = res.add_attribute("recipient", recipient.clone());
+    }
+    for (token_id, token) in tokens {
+        // still active tokens are skipped
+        if token_status(&config, &token, &env.block) != TokenStatus::Expired {
+            continue;
+        }
+        let event = match &recipient {
+            // the token is already where the policy puts it, it only leaves the index
+            Some(recipient) if token.owner == *recipient => {
+                unindex_expiration(deps.storage, &token_id, &token);
+                continue;
+            }
+            Some(recipient) => _reclaim(deps.branch(), &token_id, token, recipient)?,
+            None => _burn(deps.branch(), &token_id, &token)?,
+        };
+        res = res.add_event(event);
+    }
+
+    Ok(res)
+}
+
+pub fn fund_bounty_pool(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response<Empty>, ContractError> {
+    // only minter can fund the cleanup bounty pool
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    let bounty = config
+        .cleanup_bounty
+        .ok_or(ContractError::BountyDisabled {})?;
+    let amount = must_pay(&info, &bounty.denom)?;
+    let pool = BOUNTY_POOL
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .checked_add(amount)
+        .map_err(StdError::from)?;
+    BOUNTY_POOL.save(deps.storage, &pool)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "fund_bounty_pool")
+        .add_attribute("sender", info.sender)
+        .add_attribute("amount", amount)
+        .add_attribute("pool", pool))
+}
+
+pub fn cleanup_expired(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_ids: Vec<String>,
+) -> Result<Response<Empty>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.post_expiry == PostExpiryPolicy::Freeze {
+        return Err(ContractError::ExpiredTokensKept {});
+    }
+    let recipient = _post_expiry_recipient(deps.as_ref(), &config)?;
+
+    let mut res = Response::new()
+        .add_attribute("action", "cleanup_expired")
+        .add_attribute("sender", info.sender.clone());
+    for token_id in &token_ids {
+        let token = Cw721TimeLimited::default()
+            .tokens
+            .load(deps.storage, token_id)?;
+        // unexpired tokens are rejected rather than skipped
+        if token_status(&config, &token, &env.block) != TokenStatus::Expired {
+            return Err(ContractError::TokenNotExpired {
+                token_id: token_id.clone(),
+            });
+        }
+
+        let event = match &recipient {
+            None => _burn(deps.branch(), token_id, &token)?,
+            Some(recipient) => {
+                if token.owner == *recipient {
+                    return Err(ContractError::AlreadyCleanedUp {
+                        token_id: token_id.clone(),
+                    });
+                }
+                _reclaim(deps.branch(), token_id, token, recipient)?
+            }
+        };
+        res = res.add_event(event);
+    }
+
+    // pay the bounty for every cleaned up token, as far as the pool allows
+    if let Some(bounty) = config.cleanup_bounty {
+        let pool = BOUNTY_POOL.may_load(deps.storage)?.unwrap_or_default();
+        let reward = (bounty.amount * Uint128::from(token_ids.len() as u128)).min(pool);
+        if !reward.is_zero() {
+            BOUNTY_POOL.save(deps.storage, &(pool - reward))?;
+            res = res
+                .add_message(BankMsg::Send {
+                    to_address: info.sender.to_string(),
+                    amount: coins(reward.u128(), bounty.denom),
+                })
+                .add_attribute("bounty", reward);
+        }
+    }
+
+    Ok(res)
+}
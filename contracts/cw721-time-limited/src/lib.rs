@@ -1,4 +1,5 @@
 pub mod contract;
+pub mod error;
 pub mod execute;
 pub mod msg;
 pub mod query;
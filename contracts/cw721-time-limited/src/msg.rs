@@ -1,10 +1,18 @@
-use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{CustomMsg, Empty, Uint128};
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Binary, Coin, CustomMsg, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw721::Expiration;
+use cw_ownable::{cw_ownable_execute, cw_ownable_query};
+use cw_utils::Duration;
 
-use crate::state::Extension;
+use crate::state::{
+    Cw20Price, ExpirationKinds, ExpiredVisibility, Extension, LifetimeConfig, PendingRoyalty,
+    PendingTransfers, PostExpiryPolicy, Redemption, RoyaltyRecipient, TokenStatus, Transferability,
+};
 
 /// Message type for `instantiate` entry_point
 #[cw_serde]
+#[derive(Default)]
 pub struct InstantiateMsg {
     /// Name of the NFT contract
     pub name: String,
@@ -14,19 +22,342 @@ pub struct InstantiateMsg {
     /// This is designed for a base NFT that is controlled by an external program
     /// or contract. You will likely replace this with custom logic in custom NFTs
     pub minter: String,
-    pub royalty_percentage: Option<u64>,
+    /// The royalty paid on every sale, in basis points (1/100 of a percent)
+    pub royalty_bps: Option<u64>,
     pub royalty_payment_address: Option<String>,
     pub creator: Option<String>,
+    /// The highest royalty in basis points later rate changes can set, defaults to royalty_bps
+    pub max_royalty_bps: Option<u64>,
+    /// How long a royalty rate change waits before it can be applied,
+    /// defaults to a week and cannot be zero
+    pub royalty_timelock: Option<Duration>,
+    /// The length of one paid renewal period, required if renewal_price is set
+    pub renewal_period: Option<Duration>,
+    /// The native coin owners pay for each renewal period
+    pub renewal_price: Option<Coin>,
+    /// The address receiving renewal payments, defaults to the minter
+    pub treasury: Option<String>,
+    /// The CW20 tokens accepted as payment for minting and renewing
+    pub cw20_prices: Option<Vec<Cw20Price>>,
+    /// How long after expiring a token can still be renewed by its owner
+    pub grace_period: Option<Duration>,
+    /// The address expired tokens are reclaimed to, defaults to the minter
+    pub reclaim_vault: Option<String>,
+    /// What happens to expired tokens, defaults to reclaim
+    pub post_expiry: Option<PostExpiryPolicy>,
+    /// The reward paid per token to whoever cleans up expired tokens
+    pub cleanup_bounty: Option<Coin>,
+    /// Whether OwnerOf, AllNftInfo, Tokens and AllTokens show expired tokens, defaults to show
+    pub expired_visibility: Option<ExpiredVisibility>,
+    /// The default lifetime of new tokens and the bounds on any token lifetime
+    pub lifetime: Option<LifetimeConfig>,
+    /// The kinds of expiration tokens can be minted with, defaults to any
+    pub allowed_expiration: Option<ExpirationKinds>,
+    /// Who can move tokens waiting for activation, defaults to allow
+    pub pending_transfers: Option<PendingTransfers>,
+    /// Who can move tokens before their validity window starts, defaults to allow
+    pub not_yet_valid_transfers: Option<PendingTransfers>,
+    /// Whether owners can transfer, send and approve their tokens, defaults to always
+    pub transferability: Option<Transferability>,
+    /// The token_uri NftInfo and AllNftInfo show for expired tokens
+    pub expired_token_uri: Option<String>,
+    /// The image NftInfo and AllNftInfo show for expired tokens
+    pub expired_image: Option<String>,
+    /// Splits royalties between several addresses, the shares must add up to 10000
+    pub royalty_recipients: Option<Vec<RoyaltyRecipient>>,
 }
 
-pub type ExecuteMsg = cw721_base::ExecuteMsg<Extension, Empty>;
-pub type QueryMsg = cw721_base::QueryMsg<Cw2981QueryMsg>;
+/// This wraps the cw721_base::ExecuteMsg variants and adds the messages
+/// used to manage the lifetime of time-limited tokens
+#[allow(clippy::large_enum_variant)]
+#[cw_ownable_execute]
+#[cw_serde]
+pub enum ExecuteMsg {
+    /// Transfer is a base message to move a token to another account without triggering actions
+    TransferNft { recipient: String, token_id: String },
+    /// Send is a base message to transfer a token to a contract and trigger an action
+    /// on the receiving contract.
+    SendNft {
+        contract: String,
+        token_id: String,
+        msg: Binary,
+    },
+    /// Allows operator to transfer / send the token from the owner's account.
+    /// If expiration is set, then this allowance has a time/height limit
+    Approve {
+        spender: String,
+        token_id: String,
+        expires: Option<Expiration>,
+    },
+    /// Remove previously granted Approval
+    Revoke { spender: String, token_id: String },
+    /// Allows operator to transfer / send any token from the owner's account.
+    /// If expiration is set, then this allowance has a time/height limit
+    ApproveAll {
+        operator: String,
+        expires: Option<Expiration>,
+    },
+    /// Remove previously granted ApproveAll permission
+    RevokeAll { operator: String },
+
+    /// Mint a new NFT, can only be called by the contract minter
+    Mint {
+        /// Unique ID of the NFT
+        token_id: String,
+        /// The owner of the newly minter NFT
+        owner: String,
+        /// Universal resource identifier for this NFT
+        /// Should point to a JSON file that conforms to the ERC721
+        /// Metadata JSON Schema
+        token_uri: Option<String>,
+        /// Any custom extension used by this contract
+        extension: Extension,
+        /// The lifetime of the token in blocks or seconds, converted to an
+        /// expiration against the block the mint is executed in.
+        /// Cannot be combined with an expiration or a pending lifetime in the extension
+        lifetime: Option<Duration>,
+    },
+
+    /// Burn an NFT the sender has access to
+    Burn { token_id: String },
+
+    /// Push the expiration of a token further into the future,
+    /// can only be called by the contract minter.
+    /// The new expiration must be of the same kind (height or time)
+    /// as the current one and strictly later than it
+    ExtendExpiration {
+        token_id: String,
+        expires: Expiration,
+    },
+
+    /// Pause the expiry clock of a token, it is treated as active until unfrozen.
+    /// Can only be called by the contract minter
+    FreezeExpiry { token_id: String },
+
+    /// Resume the expiry clock of a token and push its expiration forward
+    /// by the time it was frozen, can only be called by the contract minter
+    UnfreezeExpiry { token_id: String },
+
+    /// Renew a token by paying the renewal price in native coins,
+    /// can only be called by the token owner until its grace period is over.
+    /// The expiration is pushed forward by every whole renewal period paid for
+    Renew { token_id: String },
+
+    /// Start the lifetime of a token minted with a pending lifetime,
+    /// can only be called by the token owner
+    Activate { token_id: String },
+
+    /// Pay for a mint or a renewal with an accepted CW20 token,
+    /// the embedded message must be a ReceiveMsg
+    Receive(Cw20ReceiveMsg),
+
+    /// Apply the post expiry policy to expired tokens, can only be called by the contract minter.
+    /// Either the given token_ids are handled, skipping the ones still active or already handled,
+    /// or up to limit expired tokens are looked up and handled. Expired tokens already
+    /// held by the recipient of the policy are taken out of the expiration index
+    ReclaimExpired {
+        token_ids: Option<Vec<String>>,
+        limit: Option<u32>,
+    },
+
+    /// Add the sent funds to the pool paying cleanup bounties,
+    /// can only be called by the contract minter
+    FundBountyPool {},
+
+    /// Apply the post expiry policy to the given expired tokens, unless they are kept by their holders.
+    /// Anyone can call this and is paid the cleanup bounty for every token,
+    /// the call fails if any of the tokens is not expired
+    CleanupExpired { token_ids: Vec<String> },
+
+    /// Add up to `limit` of the tokens minted before the expiration index to it,
+    /// anyone can call this after a migration until every token is indexed.
+    /// Expired and expiring tokens are only listed once they are indexed
+    IndexExpirations { limit: Option<u32> },
+
+    /// Use up `amount` uses of a token with a usage limit, can be called by
+    /// the token owner or an authorized consumer while the token is usable.
+    /// A token with no uses left is treated like an expired one
+    Consume { token_id: String, amount: u32 },
+
+    /// Add and remove the addresses allowed to consume uses of any token,
+    /// can only be called by the contract minter
+    UpdateConsumers {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+
+    /// Mark a token as redeemed, for example at the door of an event.
+    /// Can only be called by a scanner, once per token and while the token is usable
+    Redeem { token_id: String },
+
+    /// Add and remove the addresses allowed to redeem tokens,
+    /// can only be called by the contract minter
+    UpdateScanners {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+
+    /// Change the royalty terms of the collection, can only be called by the creator
+    /// or the contract minter. A new payment address takes effect immediately,
+    /// a new rate in basis points is capped by the max royalty and only proposed,
+    /// it can be applied once the royalty timelock has passed
+    UpdateRoyaltyConfig {
+        royalty_bps: Option<u64>,
+        royalty_payment_address: Option<String>,
+        /// Replaces the royalty split immediately, an empty list removes it
+        royalty_recipients: Option<Vec<RoyaltyRecipient>>,
+    },
+
+    /// Apply the proposed royalty rate once its timelock has passed,
+    /// can only be called by the creator or the contract minter
+    ApplyRoyaltyConfig {},
+
+    /// Replace the default lifetime and the lifetime bounds of the collection,
+    /// can only be called by the contract minter. Existing tokens are not changed
+    UpdateLifetimeConfig {
+        default_lifetime: Option<Duration>,
+        min_lifetime: Option<Duration>,
+        max_lifetime: Option<Duration>,
+    },
+}
+
+/// The hook messages accepted along with a CW20 payment
+#[allow(clippy::large_enum_variant)]
+#[cw_serde]
+pub enum ReceiveMsg {
+    /// Mint a new token owned by the payer, lasting one renewal period.
+    /// The metadata cannot set what only the minter can, like the usage limit or the transferability
+    Mint {
+        token_id: String,
+        token_uri: Option<String>,
+        extension: Extension,
+    },
+    /// Renew a token owned by the payer by every whole renewal period paid for
+    Renew { token_id: String },
+}
+
+/// This mirrors the cw721_base::QueryMsg variants, adding the option to list
+/// expired tokens when the collection hides them
+#[cw_ownable_query]
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    /// Return the owner of the given token, error if token does not exist
+    #[returns(cw721::OwnerOfResponse)]
+    OwnerOf {
+        token_id: String,
+        /// unset or false will filter out expired approvals, you must set to true to see them.
+        /// It also shows expired tokens if the collection hides them
+        include_expired: Option<bool>,
+    },
+    /// Return operator that can access all of the owner's tokens.
+    #[returns(cw721::ApprovalResponse)]
+    Approval {
+        token_id: String,
+        spender: String,
+        include_expired: Option<bool>,
+    },
+    /// Return approvals that a token has
+    #[returns(cw721::ApprovalsResponse)]
+    Approvals {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    /// Return approval of a given operator for all tokens of an owner, error if not set
+    #[returns(cw721::OperatorResponse)]
+    Operator {
+        owner: String,
+        operator: String,
+        include_expired: Option<bool>,
+    },
+    /// List all operators that can access all of the owner's tokens
+    #[returns(cw721::OperatorsResponse)]
+    AllOperators {
+        owner: String,
+        /// unset or false will filter out expired items, you must set to true to see them
+        include_expired: Option<bool>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Total number of tokens issued
+    #[returns(cw721::NumTokensResponse)]
+    NumTokens {},
+
+    /// With MetaData Extension.
+    /// Returns top-level metadata about the contract
+    #[returns(ContractInfoResponse)]
+    ContractInfo {},
+    /// With MetaData Extension.
+    /// Returns metadata about one particular token, based on *ERC721 Metadata JSON Schema*
+    /// but directly from the contract
+    #[returns(cw721::NftInfoResponse<Extension>)]
+    NftInfo { token_id: String },
+    /// With MetaData Extension.
+    /// Returns the result of both `NftInfo` and `OwnerOf` as one query as an optimization
+    /// for clients
+    #[returns(cw721::AllNftInfoResponse<Extension>)]
+    AllNftInfo {
+        token_id: String,
+        /// unset or false will filter out expired approvals, you must set to true to see them.
+        /// It also shows expired tokens if the collection hides them
+        include_expired: Option<bool>,
+    },
+
+    /// With Enumerable extension.
+    /// Returns all tokens owned by the given address, [] if unset.
+    /// Left out expired tokens count towards the limit, so a short page is not the last one,
+    /// VisibleTokens tells where to continue
+    #[returns(cw721::TokensResponse)]
+    Tokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        /// unset or false will filter out expired tokens if the collection hides them
+        include_expired: Option<bool>,
+    },
+    /// With Enumerable extension.
+    /// Requires pagination. Lists all token_ids controlled by the contract.
+    /// Left out expired tokens count towards the limit, so a short page is not the last one,
+    /// AllVisibleTokens tells where to continue
+    #[returns(cw721::TokensResponse)]
+    AllTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+        /// unset or false will filter out expired tokens if the collection hides them
+        include_expired: Option<bool>,
+    },
+
+    /// Return the minter
+    #[returns(cw721_base::MinterResponse)]
+    Minter {},
+
+    /// Returns the default royalty terms of the collection and its creator,
+    /// so they can be shown before a sale
+    #[returns(RoyaltyConfigResponse)]
+    RoyaltyConfig {},
+    /// Returns how the royalty owed on a sale is split between its recipients,
+    /// RoyaltyInfo only names the primary recipient
+    #[returns(RoyaltySplitsResponse)]
+    RoyaltySplits {
+        token_id: String,
+        // the denom of this sale must also be the denom returned by the query
+        sale_price: Uint128,
+    },
+
+    /// Extension query
+    #[returns(())]
+    Extension { msg: Cw2981QueryMsg },
+
+    /// Extension query on the lifetime of tokens
+    #[returns(())]
+    TimeLimited { msg: TimeLimitedQueryMsg },
+}
 
 #[cw_serde]
 pub enum Cw2981QueryMsg {
     /// Should be called on sale to see if royalties are owed
     /// by the marketplace selling the NFT, if CheckRoyalties
-    /// returns true
+    /// returns true, the address is the primary recipient of a royalty split
     /// See https://eips.ethereum.org/EIPS/eip-2981
     RoyaltyInfo {
         token_id: String,
@@ -52,6 +383,56 @@ impl Default for Cw2981QueryMsg {
 
 impl CustomMsg for Cw2981QueryMsg {}
 
+/// The queries on the lifetime, usage and redemption of tokens
+#[cw_serde]
+pub enum TimeLimitedQueryMsg {
+    /// Lists the tokens expiring before `at` in expiration order, including already expired ones.
+    /// `at` selects whether height or time based tokens are listed,
+    /// `Never` lists all tokens that expire, height based ones first
+    ExpiringBefore {
+        at: Expiration,
+        start_after: Option<ExpiryCursor>,
+        limit: Option<u32>,
+    },
+    /// Lists the tokens expired at the current block in expiration order, height based ones first.
+    /// Tokens in their grace period are left out, tokens leave the list once reclaimed or burned
+    Expired {
+        start_after: Option<ExpiryCursor>,
+        limit: Option<u32>,
+    },
+    /// Returns whether a token is usable at the current block and how long it has left
+    TokenStatus { token_id: String },
+    /// Returns the default lifetime and the lifetime bounds of the collection
+    LifetimeConfig {},
+    /// Returns what happens to tokens once they are expired
+    PostExpiryPolicy {},
+    /// Lists the addresses allowed to consume uses of any token
+    Consumers {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the addresses allowed to redeem tokens
+    Scanners {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns who redeemed a token and when, if it was redeemed
+    Redemption { token_id: String },
+    /// Lists the tokens of an owner Tokens shows, scanning up to limit tokens.
+    /// Continue from next_start_after until it is None
+    VisibleTokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the tokens AllTokens shows, scanning up to limit tokens.
+    /// Continue from next_start_after until it is None
+    AllVisibleTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
 #[cw_serde]
 pub struct RoyaltiesInfoResponse {
     pub address: String,
@@ -60,6 +441,78 @@ pub struct RoyaltiesInfoResponse {
     pub royalty_amount: Uint128,
 }
 
+#[cw_serde]
+pub struct ExpiringToken {
+    pub token_id: String,
+    pub owner: String,
+    pub expires: Expiration,
+}
+
+/// A page of token ids, with a next_start_after as long as there are more tokens to scan
+#[cw_serde]
+pub struct TokensPageResponse {
+    pub tokens: Vec<String>,
+    pub next_start_after: Option<String>,
+}
+
+/// The position of a token in expiration order, listing resumes after it
+/// even if the token has since been renewed or burned
+#[cw_serde]
+pub struct ExpiryCursor {
+    pub expires: Expiration,
+    pub token_id: String,
+}
+
+impl From<&ExpiringToken> for ExpiryCursor {
+    fn from(token: &ExpiringToken) -> Self {
+        ExpiryCursor {
+            expires: token.expires,
+            token_id: token.token_id.clone(),
+        }
+    }
+}
+
+#[cw_serde]
+pub struct ExpiringTokensResponse {
+    pub tokens: Vec<ExpiringToken>,
+}
+
+#[cw_serde]
+pub struct TokenStatusResponse {
+    pub status: TokenStatus,
+    pub expires: Option<Expiration>,
+    /// The blocks or seconds left until the token expires while it is active,
+    /// or until its grace period ends while in grace
+    pub remaining: Option<Duration>,
+}
+
+#[cw_serde]
+pub struct ConsumersResponse {
+    pub consumers: Vec<String>,
+}
+
+#[cw_serde]
+pub struct ScannersResponse {
+    pub scanners: Vec<String>,
+}
+
+#[cw_serde]
+pub struct RedemptionResponse {
+    pub redemption: Option<Redemption>,
+}
+
+#[cw_serde]
+pub struct RoyaltyPayment {
+    pub address: String,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct RoyaltySplitsResponse {
+    /// The payments add up to the royalty amount of RoyaltyInfo
+    pub payments: Vec<RoyaltyPayment>,
+}
+
 /// Shows if the contract implements royalties
 /// if royalty_payments is true, marketplaces should pay them
 #[cw_serde]
@@ -67,6 +520,26 @@ pub struct CheckRoyaltiesResponse {
     pub royalty_payments: bool,
 }
 
+#[cw_serde]
+pub struct RoyaltyConfigResponse {
+    /// The royalty paid on every sale, in basis points
+    pub royalty_bps: Option<u64>,
+    /// The address royalties are paid to
+    pub royalty_payment_address: Option<String>,
+    pub creator: Option<String>,
+    /// The highest royalty in basis points the rate can be changed to
+    pub max_royalty_bps: Option<u64>,
+    /// How long a proposed royalty rate waits before it can be applied
+    pub royalty_timelock: Option<Duration>,
+    /// The proposed royalty rate waiting for its timelock
+    pub pending_royalty: Option<PendingRoyalty>,
+    /// How royalties are split, all of them go to the royalty payment address if empty
+    pub royalty_recipients: Vec<RoyaltyRecipient>,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {}
+
 // We define a new ContractInfoResponse to add the creator field
 #[cw_serde]
 pub struct ContractInfoResponse {
@@ -1,32 +1,64 @@
-use crate::msg::{CheckRoyaltiesResponse, ContractInfoResponse, RoyaltiesInfoResponse};
-use crate::state::{Cw721TimeLimited, CREATOR};
-use cosmwasm_std::{Decimal, Deps, StdResult, Uint128};
+use crate::error::ContractError;
+use crate::msg::{
+    CheckRoyaltiesResponse, ConsumersResponse, ContractInfoResponse, ExpiringToken,
+    ExpiringTokensResponse, ExpiryCursor, RedemptionResponse, RoyaltiesInfoResponse,
+    RoyaltyConfigResponse, RoyaltyPayment, RoyaltySplitsResponse, ScannersResponse,
+    TokenStatusResponse, TokensPageResponse,
+};
+use crate::state::{
+    expired_bounds, token_status, Config, Cw721TimeLimited, ExpiredVisibility, Extension,
+    LifetimeConfig, PostExpiryPolicy, RoyaltyRecipient, TokenStatus, CONFIG, CONSUMERS, CREATOR,
+    EXPIRY_BY_HEIGHT, EXPIRY_BY_TIME, MAX_ROYALTY_BPS, SCANNERS,
+};
+use cosmwasm_std::{
+    Addr, BlockInfo, Deps, Empty, Env, Order, StdError, StdResult, Timestamp, Uint128,
+};
+use cw721::{
+    AllNftInfoResponse, Cw721Query, Expiration, NftInfoResponse, OwnerOfResponse, TokensResponse,
+};
+use cw721_base::state::TokenInfo;
+use cw_storage_plus::{Bound, Map};
+use cw_utils::Duration;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+
+/// returns the royalty owed on a sale of the token and the split it is paid by,
+/// the token's own split wins over the one of the collection
+fn _royalty_terms(
+    deps: Deps,
+    token_id: &str,
+    sale_price: Uint128,
+) -> StdResult<(Config, Uint128, Vec<RoyaltyRecipient>)> {
+    let contract = Cw721TimeLimited::default();
+    let token = contract.tokens.load(deps.storage, token_id)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    // exact at basis point precision
+    let royalty_amount =
+        sale_price.multiply_ratio(config.royalty_bps.unwrap_or_default(), MAX_ROYALTY_BPS);
+    let recipients = token
+        .extension
+        .and_then(|ext| ext.royalty_recipients)
+        .unwrap_or_else(|| config.royalty_recipients.clone());
+    Ok((config, royalty_amount, recipients))
+}
 
 /// NOTE: default behaviour here is to round down
-/// EIP2981 specifies that the rounding behaviour is at the discretion of the implementer
+/// EIP2981 specifies that the rounding behaviour is at the discretion of the implementer.
+/// The terms come from the config, which can change after the token was minted.
+/// Royalties that are split name only the primary recipient, see RoyaltySplits
 pub fn query_royalties_info(
     deps: Deps,
     token_id: String,
     sale_price: Uint128,
 ) -> StdResult<RoyaltiesInfoResponse> {
-    let contract = Cw721TimeLimited::default();
-    let token_info = contract.tokens.load(deps.storage, &token_id)?;
+    let (config, royalty_from_sale_price, recipients) =
+        _royalty_terms(deps, &token_id, sale_price)?;
 
-    let royalty_percentage = match token_info.extension {
-        Some(ref ext) => match ext.royalty_percentage {
-            Some(percentage) => Decimal::percent(percentage),
-            None => Decimal::percent(0),
-        },
-        None => Decimal::percent(0),
-    };
-    let royalty_from_sale_price = sale_price * royalty_percentage;
-
-    let royalty_address = match token_info.extension {
-        Some(ext) => match ext.royalty_payment_address {
-            Some(addr) => addr,
-            None => String::from(""),
-        },
-        None => String::from(""),
+    let royalty_address = match recipients.into_iter().next() {
+        Some(primary) => primary.address,
+        None => config.royalty_payment_address.unwrap_or_default(),
     };
 
     Ok(RoyaltiesInfoResponse {
@@ -35,8 +67,42 @@ pub fn query_royalties_info(
     })
 }
 
-/// As our default implementation here specifies royalties at token level
-/// and not at contract level, it is therefore logically true that
+/// Each recipient gets its share rounded down, what rounding leaves over
+/// goes to the primary recipient so the payments add up to the royalty amount
+pub fn query_royalty_splits(
+    deps: Deps,
+    token_id: String,
+    sale_price: Uint128,
+) -> StdResult<RoyaltySplitsResponse> {
+    let (config, royalty_amount, recipients) = _royalty_terms(deps, &token_id, sale_price)?;
+
+    if recipients.is_empty() {
+        let payments = config
+            .royalty_payment_address
+            .map(|address| RoyaltyPayment {
+                address,
+                amount: royalty_amount,
+            })
+            .into_iter()
+            .collect();
+        return Ok(RoyaltySplitsResponse { payments });
+    }
+
+    let mut payments: Vec<_> = recipients
+        .into_iter()
+        .map(|recipient| RoyaltyPayment {
+            address: recipient.address,
+            amount: royalty_amount.multiply_ratio(recipient.share_bps, MAX_ROYALTY_BPS),
+        })
+        .collect();
+    let paid: Uint128 = payments.iter().map(|payment| payment.amount).sum();
+    payments[0].amount += royalty_amount - paid;
+
+    Ok(RoyaltySplitsResponse { payments })
+}
+
+/// As the royalty terms are set at contract level but can be split differently
+/// per token, it is therefore logically true that
 /// on sale, every token managed by this contract should be checked
 /// to see if royalties are owed, and to whom. If you are importing
 /// this logic, you may want a custom implementation here
@@ -61,3 +127,459 @@ pub fn contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
         creator,
     })
 }
+
+/// The default royalty terms of the collection and its creator
+pub fn query_royalty_config(deps: Deps) -> StdResult<RoyaltyConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(RoyaltyConfigResponse {
+        royalty_bps: config.royalty_bps,
+        royalty_payment_address: config.royalty_payment_address,
+        creator: CREATOR.load(deps.storage)?,
+        max_royalty_bps: config.max_royalty_bps,
+        royalty_timelock: config.royalty_timelock,
+        pending_royalty: config.pending_royalty,
+        royalty_recipients: config.royalty_recipients,
+    })
+}
+
+/// What happens to tokens once they are expired
+pub fn query_post_expiry_policy(deps: Deps) -> StdResult<PostExpiryPolicy> {
+    Ok(CONFIG.load(deps.storage)?.post_expiry)
+}
+
+/// The default lifetime and the lifetime bounds of the collection
+pub fn query_lifetime_config(deps: Deps) -> StdResult<LifetimeConfig> {
+    Ok(CONFIG.load(deps.storage)?.lifetime)
+}
+
+/// The addresses allowed to consume uses of any token
+pub fn query_consumers(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ConsumersResponse> {
+    Ok(ConsumersResponse {
+        consumers: _addresses(deps, CONSUMERS, start_after, limit)?,
+    })
+}
+
+/// The addresses allowed to redeem tokens
+pub fn query_scanners(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ScannersResponse> {
+    Ok(ScannersResponse {
+        scanners: _addresses(deps, SCANNERS, start_after, limit)?,
+    })
+}
+
+/// lists the addresses of an address set in ascending order
+fn _addresses(
+    deps: Deps,
+    set: Map<&Addr, Empty>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<String>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+    set.keys(
+        deps.storage,
+        start_after.as_ref().map(Bound::exclusive),
+        None,
+        Order::Ascending,
+    )
+    .take(limit)
+    .map(|address| address.map(String::from))
+    .collect()
+}
+
+/// Who redeemed a token and when
+pub fn query_redemption(deps: Deps, token_id: String) -> StdResult<RedemptionResponse> {
+    let token = Cw721TimeLimited::default()
+        .tokens
+        .load(deps.storage, &token_id)?;
+    Ok(RedemptionResponse {
+        redemption: token.extension.and_then(|metadata| metadata.redemption),
+    })
+}
+
+/// The status of a token at the current block, with the lifetime it has left
+pub fn query_token_status(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+) -> StdResult<TokenStatusResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let token = Cw721TimeLimited::default()
+        .tokens
+        .load(deps.storage, &token_id)?;
+    let status = token_status(&config, &token, &env.block);
+    let (expires, pending_lifetime, frozen) = token
+        .extension
+        .map(|metadata| (metadata.expires, metadata.pending_lifetime, metadata.frozen))
+        .unwrap_or_default();
+    // the lifetime of a frozen token is counted at the block it was frozen
+    let clock = match frozen {
+        Some(frozen) => BlockInfo {
+            height: frozen.height,
+            time: frozen.time,
+            ..env.block.clone()
+        },
+        None => env.block.clone(),
+    };
+
+    let remaining = match (&status, expires) {
+        (TokenStatus::PendingActivation, _) => pending_lifetime,
+        (TokenStatus::Active | TokenStatus::NotYetValid, Some(expires)) => {
+            _remaining(&clock, &expires)
+        }
+        (TokenStatus::Grace, Some(expires)) => config
+            .grace_period
+            .and_then(|grace_period| (expires + grace_period).ok())
+            .and_then(|grace_end| _remaining(&env.block, &grace_end)),
+        _ => None,
+    };
+
+    Ok(TokenStatusResponse {
+        status,
+        expires,
+        remaining,
+    })
+}
+
+/// the blocks or seconds left at `block` until `expires`
+fn _remaining(block: &BlockInfo, expires: &Expiration) -> Option<Duration> {
+    match expires {
+        Expiration::AtHeight(height) => Some(Duration::Height(height.saturating_sub(block.height))),
+        Expiration::AtTime(time) => Some(Duration::Time(
+            time.seconds().saturating_sub(block.time.seconds()),
+        )),
+        Expiration::Never {} => None,
+    }
+}
+
+/// List the tokens expiring before `at`, whether expired or not
+pub fn query_expiring_before(
+    deps: Deps,
+    at: Expiration,
+    start_after: Option<ExpiryCursor>,
+    limit: Option<u32>,
+) -> StdResult<ExpiringTokensResponse> {
+    let (height_until, time_until) = match at {
+        Expiration::AtHeight(height) => (Some(height), None),
+        Expiration::AtTime(time) => (None, Some(time.nanos())),
+        Expiration::Never {} => (Some(u64::MAX), Some(u64::MAX)),
+    };
+    _expiring_tokens(deps, height_until, time_until, start_after, limit)
+}
+
+/// List the tokens expired at the current block, leaving out those in their grace period
+pub fn query_expired(
+    deps: Deps,
+    env: Env,
+    start_after: Option<ExpiryCursor>,
+    limit: Option<u32>,
+) -> StdResult<ExpiringTokensResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let (height_until, time_until) = expired_bounds(&config, &env.block);
+    _expiring_tokens(
+        deps,
+        Some(height_until),
+        Some(time_until),
+        start_after,
+        limit,
+    )
+}
+
+/// Pages through the expiration index, height based tokens first.
+/// Only tokens expiring strictly before `height_until` or `time_until` are listed,
+/// an index is skipped entirely if its bound is None
+fn _expiring_tokens(
+    deps: Deps,
+    height_until: Option<u64>,
+    time_until: Option<u64>,
+    start_after: Option<ExpiryCursor>,
+    limit: Option<u32>,
+) -> StdResult<ExpiringTokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // resume after the position of the cursor in the index,
+    // the token it names does not have to be there anymore
+    let mut height_until = height_until;
+    let mut height_start = None;
+    let mut time_start = None;
+    if let Some(ExpiryCursor { expires, token_id }) = &start_after {
+        match expires {
+            Expiration::AtHeight(height) => {
+                height_start = Some(Bound::exclusive((*height, token_id.as_str())))
+            }
+            Expiration::AtTime(time) => {
+                // all height based tokens come before this one
+                height_until = None;
+                time_start = Some(Bound::exclusive((time.nanos(), token_id.as_str())));
+            }
+            Expiration::Never {} => {
+                return Err(StdError::generic_err(format!(
+                    "Token {} does not expire",
+                    token_id
+                )))
+            }
+        }
+    }
+
+    let by_height = height_until.into_iter().flat_map(|until| {
+        EXPIRY_BY_HEIGHT
+            .range(
+                deps.storage,
+                height_start.clone(),
+                Some(Bound::exclusive((until, ""))),
+                Order::Ascending,
+            )
+            .map(|item| {
+                item.map(|((height, token_id), owner)| ExpiringToken {
+                    token_id,
+                    owner: owner.to_string(),
+                    expires: Expiration::AtHeight(height),
+                })
+            })
+    });
+    let by_time = time_until.into_iter().flat_map(|until| {
+        EXPIRY_BY_TIME
+            .range(
+                deps.storage,
+                time_start.clone(),
+                Some(Bound::exclusive((until, ""))),
+                Order::Ascending,
+            )
+            .map(|item| {
+                item.map(|((nanos, token_id), owner)| ExpiringToken {
+                    token_id,
+                    owner: owner.to_string(),
+                    expires: Expiration::AtTime(Timestamp::from_nanos(nanos)),
+                })
+            })
+    });
+
+    let tokens = by_height
+        .chain(by_time)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(ExpiringTokensResponse { tokens })
+}
+
+/// returns true iff the token is past its expiration and the collection hides such tokens
+fn _is_hidden(config: &Config, token: &TokenInfo<Extension>, block: &BlockInfo) -> bool {
+    config.expired_visibility != ExpiredVisibility::Show
+        && matches!(
+            token_status(config, token, block),
+            TokenStatus::Grace | TokenStatus::Expired
+        )
+}
+
+/// returns the error reported for a hidden token, depending on the visibility policy
+fn _hidden_error(config: &Config, token_id: &str) -> StdError {
+    match config.expired_visibility {
+        ExpiredVisibility::Error => StdError::generic_err(
+            ContractError::TokenExpired {
+                token_id: token_id.to_string(),
+            }
+            .to_string(),
+        ),
+        _ => StdError::not_found(format!("token {}", token_id)),
+    }
+}
+
+/// fails if the token should not be shown, unless include_expired is set
+fn _check_visible(deps: Deps, env: &Env, token_id: &str, include_expired: bool) -> StdResult<()> {
+    if include_expired {
+        return Ok(());
+    }
+    let config = CONFIG.load(deps.storage)?;
+    let token = Cw721TimeLimited::default()
+        .tokens
+        .load(deps.storage, token_id)?;
+    if _is_hidden(&config, &token, &env.block) {
+        return Err(_hidden_error(&config, token_id));
+    }
+    Ok(())
+}
+
+/// OwnerOf reporting expired tokens according to the visibility policy
+pub fn query_owner_of(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    include_expired: bool,
+) -> StdResult<OwnerOfResponse> {
+    _check_visible(deps, &env, &token_id, include_expired)?;
+    Cw721TimeLimited::default().owner_of(deps, env, token_id, include_expired)
+}
+
+/// AllNftInfo reporting expired tokens according to the visibility policy,
+/// with the expired token_uri and image once the token is expired
+pub fn query_all_nft_info(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    include_expired: bool,
+) -> StdResult<AllNftInfoResponse<Extension>> {
+    _check_visible(deps, &env, &token_id, include_expired)?;
+    let mut res = Cw721TimeLimited::default().all_nft_info(
+        deps,
+        env.clone(),
+        token_id.clone(),
+        include_expired,
+    )?;
+    _show_expired(deps, &env, &token_id, &mut res.info)?;
+    Ok(res)
+}
+
+/// NftInfo with the expired token_uri and image once the token is expired
+pub fn query_nft_info(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+) -> StdResult<NftInfoResponse<Extension>> {
+    let mut info = Cw721TimeLimited::default().nft_info(deps, token_id.clone())?;
+    _show_expired(deps, &env, &token_id, &mut info)?;
+    Ok(info)
+}
+
+/// replaces the token_uri and image of an expired token with the expired ones,
+/// those of the token first and else those of the collection
+fn _show_expired(
+    deps: Deps,
+    env: &Env,
+    token_id: &str,
+    info: &mut NftInfoResponse<Extension>,
+) -> StdResult<()> {
+    let config = CONFIG.load(deps.storage)?;
+    let token = Cw721TimeLimited::default()
+        .tokens
+        .load(deps.storage, token_id)?;
+    if !matches!(
+        token_status(&config, &token, &env.block),
+        TokenStatus::Grace | TokenStatus::Expired
+    ) {
+        return Ok(());
+    }
+
+    let (token_uri, image) = match &info.extension {
+        Some(metadata) => (
+            metadata.expired_token_uri.clone(),
+            metadata.expired_image.clone(),
+        ),
+        None => (None, None),
+    };
+    if let Some(token_uri) = token_uri.or(config.expired_token_uri) {
+        info.token_uri = Some(token_uri);
+    }
+    if let Some(image) = image.or(config.expired_image) {
+        info.extension.get_or_insert_with(Default::default).image = Some(image);
+    }
+    Ok(())
+}
+
+/// Tokens leaving out expired tokens according to the visibility policy.
+/// Hidden tokens still count towards the limit, see VisibleTokens for where to continue
+pub fn query_tokens(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    include_expired: bool,
+) -> StdResult<TokensResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    if include_expired || config.expired_visibility == ExpiredVisibility::Show {
+        return Cw721TimeLimited::default().tokens(deps, owner, start_after, limit);
+    }
+    let page = query_visible_tokens(deps, env, owner, start_after, limit)?;
+    Ok(TokensResponse {
+        tokens: page.tokens,
+    })
+}
+
+/// AllTokens leaving out expired tokens according to the visibility policy.
+/// Hidden tokens still count towards the limit, see AllVisibleTokens for where to continue
+pub fn query_all_tokens(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    include_expired: bool,
+) -> StdResult<TokensResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    if include_expired || config.expired_visibility == ExpiredVisibility::Show {
+        return Cw721TimeLimited::default().all_tokens(deps, start_after, limit);
+    }
+    let page = query_all_visible_tokens(deps, env, start_after, limit)?;
+    Ok(TokensResponse {
+        tokens: page.tokens,
+    })
+}
+
+/// The tokens of an owner not hidden by the visibility policy, with where to continue
+pub fn query_visible_tokens(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensPageResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into()));
+    let owner_addr = deps.api.addr_validate(&owner)?;
+    let tokens = Cw721TimeLimited::default()
+        .tokens
+        .idx
+        .owner
+        .prefix(owner_addr)
+        .range(deps.storage, start, None, Order::Ascending);
+    _tokens_page(&config, &env.block, tokens, limit)
+}
+
+/// All tokens not hidden by the visibility policy, with where to continue
+pub fn query_all_visible_tokens(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensPageResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into()));
+    let tokens =
+        Cw721TimeLimited::default()
+            .tokens
+            .range(deps.storage, start, None, Order::Ascending);
+    _tokens_page(&config, &env.block, tokens, limit)
+}
+
+/// Scans up to `limit` tokens and lists the ones not hidden by the visibility policy,
+/// so a page can have fewer tokens than the limit while there are more to scan
+fn _tokens_page(
+    config: &Config,
+    block: &BlockInfo,
+    tokens: impl Iterator<Item = StdResult<(String, TokenInfo<Extension>)>>,
+    limit: Option<u32>,
+) -> StdResult<TokensPageResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let scanned = tokens.take(limit).collect::<StdResult<Vec<_>>>()?;
+    let next_start_after = match scanned.last() {
+        Some((token_id, _)) if scanned.len() == limit => Some(token_id.clone()),
+        _ => None,
+    };
+    let tokens = scanned
+        .into_iter()
+        .filter(|(_, token)| !_is_hidden(config, token, block))
+        .map(|(token_id, _)| token_id)
+        .collect();
+
+    Ok(TokensPageResponse {
+        tokens,
+        next_start_after,
+    })
+}
@@ -1,9 +1,10 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Empty;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, BlockInfo, Coin, Empty, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::{Bound, Item, Map};
+use cw_utils::{Duration, Scheduled};
 
 use cw721::Expiration;
-use cw721_base::Cw721Contract;
+use cw721_base::{state::TokenInfo, Cw721Contract};
 
 use crate::msg::Cw2981QueryMsg;
 
@@ -32,24 +33,445 @@ pub struct Metadata {
     pub background_color: Option<String>,
     pub animation_url: Option<String>,
     pub youtube_url: Option<String>,
-    /// This is how much the minter takes as a cut when sold
-    /// royalties are owed on this token if it is Some
+    /// Deprecated whole percent royalty copied from the config by older versions,
+    /// royalties are paid as the config says
     pub royalty_percentage: Option<u64>,
-    /// The payment address, may be different to or the same
-    /// as the minter addr
-    /// question: how do we validate this?
+    /// Deprecated payment address copied from the config by older versions,
+    /// royalties are paid as the config says
     pub royalty_payment_address: Option<String>,
     // the expiration time of the token
     pub expires: Option<Expiration>,
+    /// The lifetime the token gets when its owner first activates it,
+    /// the token has no expiration until then
+    pub pending_lifetime: Option<Duration>,
+    /// The point from which the token can be used, it is not yet valid before,
+    /// only the minter can set it
+    pub valid_from: Option<Scheduled>,
+    /// Set while the minter has paused the expiry clock of the token
+    pub frozen: Option<FrozenClock>,
+    /// How many times the token can be consumed, it has no usage limit if None,
+    /// only the minter can set it
+    pub max_uses: Option<u32>,
+    /// How many uses are left, set from max_uses at mint
+    pub uses_remaining: Option<u32>,
+    /// Set once the token has been redeemed by a scanner
+    pub redemption: Option<Redemption>,
+    /// Overrides the transferability policy of the collection for this token,
+    /// only the minter can set it
+    pub transferability: Option<Transferability>,
+    /// Replaces the token_uri of the collection shown once the token is expired
+    pub expired_token_uri: Option<String>,
+    /// Replaces the image of the collection shown once the token is expired
+    pub expired_image: Option<String>,
+    /// Replaces the royalty recipients of the collection for this token,
+    /// only the minter can set it
+    pub royalty_recipients: Option<Vec<RoyaltyRecipient>>,
 }
 
+/// Who redeemed a token and when
+#[cw_serde]
+pub struct Redemption {
+    pub scanner: Addr,
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+/// The block at which the expiry clock of a token was paused
+#[cw_serde]
+pub struct FrozenClock {
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+impl FrozenClock {
+    /// returns how far `expires` has to move to credit the time frozen until `block`
+    pub fn credit(&self, block: &BlockInfo, expires: &Expiration) -> Option<Duration> {
+        match expires {
+            Expiration::AtHeight(_) => {
+                Some(Duration::Height(block.height.saturating_sub(self.height)))
+            }
+            Expiration::AtTime(_) => Some(Duration::Time(
+                block.time.seconds().saturating_sub(self.time.seconds()),
+            )),
+            Expiration::Never {} => None,
+        }
+    }
+}
+
+/// Royalties are counted in basis points, 10000 of them make up the sale price
+pub const MAX_ROYALTY_BPS: u64 = 10_000;
+/// How long a royalty rate change waits if the collection sets no timelock, a week
+pub const DEFAULT_ROYALTY_TIMELOCK: Duration = Duration::Time(7 * 24 * 60 * 60);
+
 #[cw_serde]
 #[derive(Default)]
 pub struct Config {
+    /// The royalty paid on every sale, in basis points
+    pub royalty_bps: Option<u64>,
+    /// Deprecated whole percent royalty from before basis points,
+    /// moved into royalty_bps by migrate
     pub royalty_percentage: Option<u64>,
     pub royalty_payment_address: Option<String>,
+    /// The highest royalty in basis points the rate can be changed to,
+    /// the rate cannot be raised if None
+    pub max_royalty_bps: Option<u64>,
+    /// How long a proposed royalty rate waits before it can be applied,
+    /// the default timelock applies if None
+    pub royalty_timelock: Option<Duration>,
+    /// The royalty rate change waiting for its timelock to pass
+    pub pending_royalty: Option<PendingRoyalty>,
+    /// The length of one paid renewal period, in blocks or seconds
+    pub renewal_period: Option<Duration>,
+    /// The native coin owners pay for each renewal period,
+    /// self-service renewal is disabled if this is None
+    pub renewal_price: Option<Coin>,
+    /// Where renewal payments are forwarded, defaults to the minter
+    pub treasury: Option<Addr>,
+    /// The CW20 tokens accepted as payment and their prices
+    #[serde(default)]
+    pub cw20_prices: Vec<Cw20Price>,
+    /// How long after expiring a token can still be renewed by its owner,
+    /// only applies to tokens whose expiration is of the same kind
+    pub grace_period: Option<Duration>,
+    /// Where expired tokens are reclaimed to, defaults to the minter
+    pub reclaim_vault: Option<Addr>,
+    /// What happens to expired tokens
+    #[serde(default)]
+    pub post_expiry: PostExpiryPolicy,
+    /// The reward paid per token to whoever cleans up expired tokens,
+    /// taken from the pool funded by the minter
+    pub cleanup_bounty: Option<Coin>,
+    /// How the standard ownership queries treat tokens past their expiration
+    #[serde(default)]
+    pub expired_visibility: ExpiredVisibility,
+    /// The default lifetime of new tokens and the bounds on any token lifetime
+    #[serde(default)]
+    pub lifetime: LifetimeConfig,
+    /// The kinds of expiration tokens of this collection can be minted with
+    #[serde(default)]
+    pub allowed_expiration: ExpirationKinds,
+    /// Who can move tokens that are waiting for activation
+    #[serde(default)]
+    pub pending_transfers: PendingTransfers,
+    /// Who can move tokens whose validity window has not started yet
+    #[serde(default)]
+    pub not_yet_valid_transfers: PendingTransfers,
+    /// Whether owners can transfer, send and approve their tokens,
+    /// tokens can override it in their metadata
+    #[serde(default)]
+    pub transferability: Transferability,
+    /// The token_uri shown instead of the stored one once a token is expired
+    pub expired_token_uri: Option<String>,
+    /// The image shown instead of the stored one once a token is expired
+    pub expired_image: Option<String>,
+    /// How royalties are split, all of them go to the royalty payment address if empty
+    #[serde(default)]
+    pub royalty_recipients: Vec<RoyaltyRecipient>,
+}
+
+/// Whether the owner of a token can move it
+#[cw_serde]
+#[derive(Default)]
+pub enum Transferability {
+    /// The owner, approved spenders and operators can move the token
+    #[default]
+    Always,
+    /// The token is bound to its owner, only the minter can move it
+    Never,
+    /// Like Never, except that once the token is expired its holder
+    /// can hand it back to the minter or the reclaim vault
+    ExpiredToMinter,
+}
+
+impl Transferability {
+    /// the policy that applies to a token, its own or else the one of the collection
+    pub fn of(config: &Config, token: &TokenInfo<Extension>) -> Transferability {
+        token
+            .extension
+            .as_ref()
+            .and_then(|metadata| metadata.transferability.clone())
+            .unwrap_or_else(|| config.transferability.clone())
+    }
+}
+
+/// Who can move a token that cannot be used yet, because it waits for its owner
+/// to activate it or its validity window has not started
+#[cw_serde]
+#[derive(Default)]
+pub enum PendingTransfers {
+    /// The owner, approved spenders and operators, like an active token
+    #[default]
+    Allow,
+    /// Only the minter, the token stays with its owner until it can be used
+    MinterOnly,
+}
+
+/// The kinds of expiration a collection accepts at mint
+#[cw_serde]
+#[derive(Default)]
+pub enum ExpirationKinds {
+    /// Only block height expirations
+    Height,
+    /// Only block time expirations
+    Time,
+    /// Block height or block time expirations, tokens must expire
+    HeightOrTime,
+    /// Any expiration, including tokens that never expire
+    #[default]
+    Any,
+}
+
+impl ExpirationKinds {
+    /// returns whether a token with this expiration can be minted,
+    /// a missing expiration counts as `Never`
+    pub fn allows(&self, expires: &Expiration) -> bool {
+        matches!(
+            (self, expires),
+            (ExpirationKinds::Any, _)
+                | (ExpirationKinds::Height, Expiration::AtHeight(_))
+                | (ExpirationKinds::Time, Expiration::AtTime(_))
+                | (
+                    ExpirationKinds::HeightOrTime,
+                    Expiration::AtHeight(_) | Expiration::AtTime(_)
+                )
+        )
+    }
+}
+
+/// A proposed royalty rate and when it can be applied
+#[cw_serde]
+pub struct PendingRoyalty {
+    pub royalty_bps: u64,
+    pub applicable_at: Expiration,
+}
+
+/// Collection rules on how long tokens live, counted from the block that
+/// mints or renews them. A bound only accepts expirations of its own kind
+#[cw_serde]
+#[derive(Default)]
+pub struct LifetimeConfig {
+    /// The lifetime given to tokens minted without an expiration
+    pub default_lifetime: Option<Duration>,
+    /// The shortest lifetime a token can be minted or renewed with
+    pub min_lifetime: Option<Duration>,
+    /// The longest lifetime a token can be minted or renewed with,
+    /// tokens that never expire are rejected if this is set
+    pub max_lifetime: Option<Duration>,
+}
+
+/// How OwnerOf, AllNftInfo, Tokens and AllTokens treat tokens past their expiration,
+/// queries setting include_expired always see them
+#[cw_serde]
+#[derive(Default)]
+pub enum ExpiredVisibility {
+    /// Expired tokens are reported like active ones
+    #[default]
+    Show,
+    /// Expired tokens are left out of lists and reported as not found
+    Hide,
+    /// Expired tokens are left out of lists and reported with an expired error
+    Error,
+}
+
+/// What happens to a token once it is expired, applied by the minter's reclaim,
+/// the permissionless cleanup and the permission checks
+#[cw_serde]
+#[derive(Default)]
+pub enum PostExpiryPolicy {
+    /// The token is moved back to the reclaim vault or the minter,
+    /// the minter can also move it
+    #[default]
+    Reclaim,
+    /// The token is burned, the minter can also burn it
+    Burn,
+    /// The token is handed to the creator of the collection
+    RevertToCreator,
+    /// The token stays with its holder as a souvenir and cannot be moved anymore
+    Freeze,
+}
+
+/// One of the addresses royalties are split between, the first recipient
+/// of a split is the primary one
+#[cw_serde]
+pub struct RoyaltyRecipient {
+    pub address: String,
+    /// The part of the royalty paid to this address, in basis points
+    pub share_bps: u64,
+}
+
+#[cw_serde]
+pub struct Cw20Price {
+    /// The address of the accepted CW20 contract
+    pub address: String,
+    /// The amount paid to mint a token lasting one renewal period,
+    /// paid minting with this token is disabled if this is None
+    pub mint_amount: Option<Uint128>,
+    /// The amount paid for each renewal period,
+    /// renewing with this token is disabled if this is None
+    pub renewal_amount: Option<Uint128>,
+}
+
+/// The lifecycle state of a token at a given block
+#[cw_serde]
+pub enum TokenStatus {
+    /// The token has not reached its expiration yet
+    Active,
+    /// The token is expired, it cannot be moved but its owner can still renew it
+    Grace,
+    /// The token is expired and its grace period, if any, is over
+    Expired,
+    /// The token has no expiration
+    NeverExpires,
+    /// The token waits for its owner to activate it and start its lifetime
+    PendingActivation,
+    /// The validity window of the token has not started yet
+    NotYetValid,
+    /// The token has no uses left, it is treated like an expired token
+    UsedUp,
+}
+
+pub fn token_status(
+    config: &Config,
+    token: &TokenInfo<Extension>,
+    block: &BlockInfo,
+) -> TokenStatus {
+    let used_up = token
+        .extension
+        .as_ref()
+        .map_or(false, |metadata| metadata.uses_remaining == Some(0));
+    match _clock_status(config, token, block) {
+        TokenStatus::Active | TokenStatus::NeverExpires if used_up => TokenStatus::UsedUp,
+        status => status,
+    }
+}
+
+/// the status of a token from its validity window and expiration alone
+fn _clock_status(config: &Config, token: &TokenInfo<Extension>, block: &BlockInfo) -> TokenStatus {
+    let expires = match token.extension.as_ref() {
+        Some(Metadata {
+            pending_lifetime: Some(_),
+            ..
+        }) => return TokenStatus::PendingActivation,
+        Some(Metadata {
+            valid_from: Some(valid_from),
+            ..
+        }) if !valid_from.is_triggered(block) => return TokenStatus::NotYetValid,
+        // a frozen token stays active until its clock is resumed
+        Some(Metadata {
+            expires: Some(Expiration::AtHeight(_) | Expiration::AtTime(_)),
+            frozen: Some(_),
+            ..
+        }) => return TokenStatus::Active,
+        Some(Metadata {
+            expires: Some(expires),
+            ..
+        }) if *expires != Expiration::Never {} => *expires,
+        _ => return TokenStatus::NeverExpires,
+    };
+    if !expires.is_expired(block) {
+        return TokenStatus::Active;
+    }
+    match config
+        .grace_period
+        .map(|grace_period| expires + grace_period)
+    {
+        Some(Ok(grace_end)) if !grace_end.is_expired(block) => TokenStatus::Grace,
+        _ => TokenStatus::Expired,
+    }
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
+// The funds available to pay cleanup bounties, in the denom of the bounty
+pub const BOUNTY_POOL: Item<Uint128> = Item::new("bounty_pool");
 // Some collection may want to have the creator different from the minter
 pub const CREATOR: Item<Option<String>> = Item::new("creator");
+// The addresses besides the owners allowed to consume uses of tokens
+pub const CONSUMERS: Map<&Addr, Empty> = Map::new("consumers");
+// The addresses allowed to redeem tokens
+pub const SCANNERS: Map<&Addr, Empty> = Map::new("scanners");
+
+// Tokens expiring at a block height, ordered by (height, token_id), with their owner
+pub const EXPIRY_BY_HEIGHT: Map<(u64, &str), Addr> = Map::new("expiry_by_height");
+// Tokens expiring at a block time, ordered by (nanoseconds, token_id), with their owner
+pub const EXPIRY_BY_TIME: Map<(u64, &str), Addr> = Map::new("expiry_by_time");
+// Set after a migration until every token is indexed, the token id indexing continues after
+pub const INDEX_BACKFILL: Item<Option<String>> = Item::new("index_backfill");
+
+/// adds a token to the expiration index or updates its owner there,
+/// tokens that never expire or are frozen are not indexed
+pub fn index_expiration(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    token: &TokenInfo<Extension>,
+) -> StdResult<()> {
+    match _indexed_expiration(token) {
+        Some(Expiration::AtHeight(height)) => {
+            EXPIRY_BY_HEIGHT.save(storage, (height, token_id), &token.owner)
+        }
+        Some(Expiration::AtTime(time)) => {
+            EXPIRY_BY_TIME.save(storage, (time.nanos(), token_id), &token.owner)
+        }
+        Some(Expiration::Never {}) | None => Ok(()),
+    }
+}
+
+/// the expiration a token is indexed under, frozen tokens are left out of the index
+fn _indexed_expiration(token: &TokenInfo<Extension>) -> Option<Expiration> {
+    match token.extension.as_ref() {
+        Some(Metadata {
+            expires,
+            frozen: None,
+            ..
+        }) => *expires,
+        _ => None,
+    }
+}
+
+/// removes a token from the expiration index, must be called with the token as it was indexed
+pub fn unindex_expiration(storage: &mut dyn Storage, token_id: &str, token: &TokenInfo<Extension>) {
+    match _indexed_expiration(token) {
+        Some(Expiration::AtHeight(height)) => EXPIRY_BY_HEIGHT.remove(storage, (height, token_id)),
+        Some(Expiration::AtTime(time)) => EXPIRY_BY_TIME.remove(storage, (time.nanos(), token_id)),
+        Some(Expiration::Never {}) | None => {}
+    }
+}
+
+/// returns the heights and the times in nanos that indexed tokens must expire before
+/// to be expired at `block`, tokens still in their grace period are not
+pub fn expired_bounds(config: &Config, block: &BlockInfo) -> (u64, u64) {
+    // a grace period only applies to tokens expiring in the same kind
+    let (grace_blocks, grace_nanos) = match config.grace_period {
+        Some(Duration::Height(blocks)) => (blocks, 0),
+        Some(Duration::Time(seconds)) => (0, seconds.saturating_mul(1_000_000_000)),
+        None => (0, 0),
+    };
+    (
+        (block.height + 1).saturating_sub(grace_blocks),
+        (block.time.nanos() + 1).saturating_sub(grace_nanos),
+    )
+}
+
+/// iterates over the ids of the tokens expired at `block` in expiration order,
+/// height based tokens first
+pub fn expired_token_ids<'a>(
+    storage: &'a dyn Storage,
+    config: &Config,
+    block: &BlockInfo,
+) -> impl Iterator<Item = StdResult<String>> + 'a {
+    let (height_until, time_until) = expired_bounds(config, block);
+    let by_height = EXPIRY_BY_HEIGHT.range(
+        storage,
+        None,
+        Some(Bound::exclusive((height_until, ""))),
+        Order::Ascending,
+    );
+    let by_time = EXPIRY_BY_TIME.range(
+        storage,
+        None,
+        Some(Bound::exclusive((time_until, ""))),
+        Order::Ascending,
+    );
+    by_height
+        .chain(by_time)
+        .map(|item| item.map(|((_, token_id), _)| token_id))
+}
@@ -1,16 +1,33 @@
 #[cfg(test)]
-use crate::contract::{execute, instantiate, query};
+use crate::contract::{execute, instantiate, migrate, query};
+use crate::error::ContractError;
 use crate::msg::{
-    CheckRoyaltiesResponse, Cw2981QueryMsg, ExecuteMsg, InstantiateMsg, QueryMsg,
-    RoyaltiesInfoResponse,
+    CheckRoyaltiesResponse, ConsumersResponse, Cw2981QueryMsg, ExecuteMsg, ExpiringToken,
+    ExpiringTokensResponse, ExpiryCursor, InstantiateMsg, MigrateMsg, QueryMsg, ReceiveMsg,
+    RedemptionResponse, RoyaltiesInfoResponse, RoyaltyConfigResponse, RoyaltyPayment,
+    RoyaltySplitsResponse, ScannersResponse, TimeLimitedQueryMsg, TokenStatusResponse,
+    TokensPageResponse,
 };
 use crate::query::{check_royalties, query_royalties_info};
-use crate::state::{Cw721TimeLimited, Metadata};
+use crate::state::{
+    expired_token_ids, Cw20Price, Cw721TimeLimited, ExpirationKinds, ExpiredVisibility, Extension,
+    FrozenClock, LifetimeConfig, Metadata, PendingRoyalty, PendingTransfers, PostExpiryPolicy,
+    Redemption, RoyaltyRecipient, TokenStatus, Transferability, CONFIG, DEFAULT_ROYALTY_TIMELOCK,
+    EXPIRY_BY_HEIGHT, EXPIRY_BY_TIME,
+};
 
-use cosmwasm_std::{from_binary, to_binary, Uint128};
+use cosmwasm_std::{
+    coin, coins, from_binary, to_binary, Addr, BankMsg, Deps, Order, StdError, StdResult, SubMsg,
+    Uint128, WasmMsg,
+};
 
 use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-use cw721::{Cw721Query, Expiration};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw721::{
+    AllNftInfoResponse, Cw721Query, Expiration, NftInfoResponse, OwnerOfResponse, TokensResponse,
+};
+use cw_ownable::OwnershipError;
+use cw_utils::{Duration, Scheduled};
 
 const CREATOR: &str = "minter";
 
@@ -20,22 +37,21 @@ fn use_metadata_extension() {
     let contract = Cw721TimeLimited::default();
 
     let info = mock_info(CREATOR, &[]);
-    // let royalty_percentage = 101
+    // let royalty_bps = 10001
     let init_msg = InstantiateMsg {
         name: "SpaceShips".to_string(),
         symbol: "SPACE".to_string(),
         minter: CREATOR.to_string(),
-        royalty_percentage: Some(50),
+        royalty_bps: Some(5000),
         royalty_payment_address: Some("john".to_string()),
         creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
     };
     instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
 
     let expected_extension = Some(Metadata {
         description: Some("Spaceship with Warp Drive".into()),
         name: Some("Starship USS Enterprise".to_string()),
-        royalty_percentage: Some(50),
-        royalty_payment_address: Some("john".to_string()),
         ..Metadata::default()
     });
 
@@ -50,6 +66,7 @@ fn use_metadata_extension() {
             name: Some("Starship USS Enterprise".to_string()),
             ..Metadata::default()
         }),
+        lifetime: None,
     };
     execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
 
@@ -67,14 +84,15 @@ fn validate_royalty_information() {
     let _contract = Cw721TimeLimited::default();
 
     let info = mock_info(CREATOR, &[]);
-    // let royalty_percentage = 101
+    // let royalty_bps = 10001
     let init_msg = InstantiateMsg {
         name: "SpaceShips".to_string(),
         symbol: "SPACE".to_string(),
         minter: CREATOR.to_string(),
-        royalty_percentage: Some(101),
+        royalty_bps: Some(10001),
         royalty_payment_address: Some("john".to_string()),
         creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
     };
     // instantiate will fail
     let res = instantiate(deps.as_mut(), mock_env(), info, init_msg);
@@ -91,9 +109,10 @@ fn not_allow_setting_royalty_when_minting() {
         name: "SpaceShips".to_string(),
         symbol: "SPACE".to_string(),
         minter: CREATOR.to_string(),
-        royalty_percentage: Some(50),
+        royalty_bps: Some(5000),
         royalty_payment_address: Some("john".to_string()),
         creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
     };
     instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
 
@@ -110,6 +129,7 @@ fn not_allow_setting_royalty_when_minting() {
             royalty_payment_address: Some("john".to_string()),
             ..Metadata::default()
         }),
+        lifetime: None,
     };
     let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
     assert!(res.is_err());
@@ -125,9 +145,10 @@ fn check_royalties_response() {
         name: "SpaceShips".to_string(),
         symbol: "SPACE".to_string(),
         minter: CREATOR.to_string(),
-        royalty_percentage: Some(50),
+        royalty_bps: Some(5000),
         royalty_payment_address: Some("john".to_string()),
         creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
     };
     instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
 
@@ -142,6 +163,7 @@ fn check_royalties_response() {
             name: Some("Starship USS Enterprise".to_string()),
             ..Metadata::default()
         }),
+        lifetime: None,
     };
     execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
 
@@ -171,9 +193,10 @@ fn check_token_royalties() {
         name: "SpaceShips".to_string(),
         symbol: "SPACE".to_string(),
         minter: CREATOR.to_string(),
-        royalty_percentage: Some(10),
+        royalty_bps: Some(1000),
         royalty_payment_address: Some(royalty_payment_address.clone()),
         creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
     };
     instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
 
@@ -188,6 +211,7 @@ fn check_token_royalties() {
             name: Some("Starship USS Enterprise".to_string()),
             ..Metadata::default()
         }),
+        lifetime: None,
     };
     execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
 
@@ -222,6 +246,7 @@ fn check_token_royalties() {
             name: Some("Starship USS Voyager".to_string()),
             ..Metadata::default()
         }),
+        lifetime: None,
     };
     execute(deps.as_mut(), mock_env(), info, voyager_exec_msg).unwrap();
 
@@ -250,9 +275,10 @@ fn check_token_without_royalties() {
         name: "SpaceShips".to_string(),
         symbol: "SPACE".to_string(),
         minter: CREATOR.to_string(),
-        royalty_percentage: None,
+        royalty_bps: None,
         royalty_payment_address: None,
         creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
     };
     instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
 
@@ -267,6 +293,7 @@ fn check_token_without_royalties() {
             name: Some("Starship USS Enterprise".to_string()),
             ..Metadata::default()
         }),
+        lifetime: None,
     };
     execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
 
@@ -298,9 +325,10 @@ fn check_token_without_extension() {
         name: "SpaceShips".to_string(),
         symbol: "SPACE".to_string(),
         minter: CREATOR.to_string(),
-        royalty_percentage: None,
+        royalty_bps: None,
         royalty_payment_address: None,
         creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
     };
     instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
 
@@ -311,6 +339,7 @@ fn check_token_without_extension() {
         owner: "jeanluc".to_string(),
         token_uri: Some("https://starships.example.com/Starship/Enterprise.json".into()),
         extension: None,
+        lifetime: None,
     };
     execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
 
@@ -333,6 +362,489 @@ fn check_token_without_extension() {
     assert_eq!(query_res, expected);
 }
 
+#[test]
+fn query_royalty_config() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: Some(1000),
+        royalty_payment_address: Some("jeanluc".to_string()),
+        creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+    let res: RoyaltyConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::RoyaltyConfig {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        res,
+        RoyaltyConfigResponse {
+            royalty_bps: Some(1000),
+            royalty_payment_address: Some("jeanluc".to_string()),
+            creator: Some("creator".to_string()),
+            max_royalty_bps: Some(1000),
+            royalty_timelock: Some(DEFAULT_ROYALTY_TIMELOCK),
+            pending_royalty: None,
+            royalty_recipients: vec![],
+        }
+    );
+}
+
+#[test]
+fn update_royalty_config_with_timelock() {
+    let mut deps = mock_dependencies();
+
+    // the initial rate cannot be above the cap
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: Some(1000),
+        royalty_payment_address: Some("jeanluc".to_string()),
+        creator: Some("creator".to_string()),
+        max_royalty_bps: Some(500),
+        royalty_timelock: Some(Duration::Height(100)),
+        ..InstantiateMsg::default()
+    };
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        init_msg.clone(),
+    )
+    .unwrap_err();
+
+    // and rate changes cannot skip the timelock
+    let res = instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        InstantiateMsg {
+            max_royalty_bps: Some(2000),
+            royalty_timelock: Some(Duration::Height(0)),
+            ..init_msg.clone()
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Std(StdError::generic_err("Royalty timelock must be non-zero"))
+    );
+
+    let init_msg = InstantiateMsg {
+        max_royalty_bps: Some(2000),
+        ..init_msg
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), init_msg).unwrap();
+
+    let token_id = "Enterprise";
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: "john".to_string(),
+        token_uri: None,
+        extension: None,
+        lifetime: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), exec_msg).unwrap();
+
+    // only the creator or the minter can change the royalties
+    let update_msg = ExecuteMsg::UpdateRoyaltyConfig {
+        royalty_bps: None,
+        royalty_payment_address: Some("picard".to_string()),
+        royalty_recipients: None,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("john", &[]),
+        update_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::NotCreatorOrMinter {});
+
+    // the payment address changes immediately, also for minted tokens
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        update_msg,
+    )
+    .unwrap();
+    let res = query_royalties_info(deps.as_ref(), token_id.to_string(), Uint128::new(100)).unwrap();
+    assert_eq!(
+        res,
+        RoyaltiesInfoResponse {
+            address: "picard".to_string(),
+            royalty_amount: Uint128::new(10),
+        }
+    );
+
+    // the rate is capped
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::UpdateRoyaltyConfig {
+            royalty_bps: Some(2100),
+            royalty_payment_address: None,
+            royalty_recipients: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::RoyaltyAboveMax { max: 2000 });
+
+    // nothing to apply yet
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::ApplyRoyaltyConfig {},
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::NoPendingRoyalty {});
+
+    // a new rate is only proposed
+    let env = mock_env();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::UpdateRoyaltyConfig {
+            royalty_bps: Some(2000),
+            royalty_payment_address: None,
+            royalty_recipients: None,
+        },
+    )
+    .unwrap();
+    let res: RoyaltyConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::RoyaltyConfig {}).unwrap())
+            .unwrap();
+    assert_eq!(res.royalty_bps, Some(1000));
+    assert_eq!(
+        res.pending_royalty,
+        Some(PendingRoyalty {
+            royalty_bps: 2000,
+            applicable_at: Expiration::AtHeight(env.block.height + 100),
+        })
+    );
+
+    // and cannot be applied before the timelock passes
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::ApplyRoyaltyConfig {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::RoyaltyTimelocked {
+            applicable_at: Expiration::AtHeight(env.block.height + 100).to_string(),
+        }
+    );
+
+    let mut later = mock_env();
+    later.block.height += 100;
+    execute(
+        deps.as_mut(),
+        later,
+        mock_info("creator", &[]),
+        ExecuteMsg::ApplyRoyaltyConfig {},
+    )
+    .unwrap();
+    let res = query_royalties_info(deps.as_ref(), token_id.to_string(), Uint128::new(100)).unwrap();
+    assert_eq!(res.royalty_amount, Uint128::new(20));
+    let res: RoyaltyConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::RoyaltyConfig {}).unwrap())
+            .unwrap();
+    assert_eq!(res.pending_royalty, None);
+}
+
+#[test]
+fn split_royalties_between_recipients() {
+    let mut deps = mock_dependencies();
+
+    // the shares must add up to the whole royalty
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: Some(1000),
+        royalty_payment_address: Some("jeanluc".to_string()),
+        royalty_recipients: Some(vec![
+            RoyaltyRecipient {
+                address: "artist".to_string(),
+                share_bps: 7000,
+            },
+            RoyaltyRecipient {
+                address: "platform".to_string(),
+                share_bps: 2000,
+            },
+        ]),
+        ..InstantiateMsg::default()
+    };
+    let err = instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        init_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::RoyaltySharesMismatch { total: 9000 });
+
+    let init_msg = InstantiateMsg {
+        royalty_recipients: Some(vec![
+            RoyaltyRecipient {
+                address: "artist".to_string(),
+                share_bps: 6667,
+            },
+            RoyaltyRecipient {
+                address: "platform".to_string(),
+                share_bps: 3333,
+            },
+        ]),
+        ..init_msg
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), init_msg).unwrap();
+
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Enterprise".to_string(),
+        owner: "john".to_string(),
+        token_uri: None,
+        extension: None,
+        lifetime: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), exec_msg).unwrap();
+
+    // RoyaltyInfo names the primary recipient
+    let res =
+        query_royalties_info(deps.as_ref(), "Enterprise".to_string(), Uint128::new(1000)).unwrap();
+    assert_eq!(
+        res,
+        RoyaltiesInfoResponse {
+            address: "artist".to_string(),
+            royalty_amount: Uint128::new(100),
+        }
+    );
+
+    // the remainder of the rounding goes to the primary recipient
+    let query_msg = QueryMsg::RoyaltySplits {
+        token_id: "Enterprise".to_string(),
+        sale_price: Uint128::new(1000),
+    };
+    let res: RoyaltySplitsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+    assert_eq!(
+        res.payments,
+        vec![
+            RoyaltyPayment {
+                address: "artist".to_string(),
+                amount: Uint128::new(67),
+            },
+            RoyaltyPayment {
+                address: "platform".to_string(),
+                amount: Uint128::new(33),
+            },
+        ]
+    );
+
+    // a token can be minted with its own split
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Voyager".to_string(),
+        owner: "john".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            royalty_recipients: Some(vec![RoyaltyRecipient {
+                address: "janeway".to_string(),
+                share_bps: 10000,
+            }]),
+            ..Metadata::default()
+        }),
+        lifetime: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), exec_msg).unwrap();
+    let query_msg = QueryMsg::RoyaltySplits {
+        token_id: "Voyager".to_string(),
+        sale_price: Uint128::new(1000),
+    };
+    let res: RoyaltySplitsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+    assert_eq!(
+        res.payments,
+        vec![RoyaltyPayment {
+            address: "janeway".to_string(),
+            amount: Uint128::new(100),
+        }]
+    );
+
+    // removing the split pays everything to the payment address
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::UpdateRoyaltyConfig {
+            royalty_bps: None,
+            royalty_payment_address: None,
+            royalty_recipients: Some(vec![]),
+        },
+    )
+    .unwrap();
+    let res =
+        query_royalties_info(deps.as_ref(), "Enterprise".to_string(), Uint128::new(1000)).unwrap();
+    assert_eq!(res.address, "jeanluc".to_string());
+}
+
+#[test]
+fn migrate_royalties_and_expiration_index() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: Some(250),
+        royalty_payment_address: Some("jeanluc".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), init_msg).unwrap();
+
+    for token_id in ["Enterprise", "Voyager"] {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "john".to_string(),
+            token_uri: None,
+            extension: None,
+            lifetime: Some(Duration::Height(100)),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), exec_msg).unwrap();
+    }
+
+    // 2.5% royalties are exact
+    let res =
+        query_royalties_info(deps.as_ref(), "Enterprise".to_string(), Uint128::new(1000)).unwrap();
+    assert_eq!(res.royalty_amount, Uint128::new(25));
+
+    // store the config and tokens the way the first version did, in whole percent
+    // and without an expiration index
+    let expires = mock_env().block.height + 100;
+    let storage = deps.as_mut().storage;
+    storage.set(
+        CONFIG.as_slice(),
+        br#"{"royalty_percentage":10,"royalty_payment_address":"jeanluc"}"#,
+    );
+    for token_id in ["Enterprise", "Voyager"] {
+        let token = format!(
+            r#"{{"owner":"john","approvals":[],"token_uri":null,"extension":{{"image":null,"image_data":null,"external_url":null,"description":null,"name":null,"attributes":null,"background_color":null,"animation_url":null,"youtube_url":null,"royalty_percentage":10,"royalty_payment_address":"jeanluc","expires":{{"at_height":{}}}}}}}"#,
+            expires
+        );
+        storage.set(&contract.tokens.key(token_id), token.as_bytes());
+        EXPIRY_BY_HEIGHT.remove(storage, (expires, token_id));
+    }
+
+    // only this contract can be migrated from
+    cw2::set_contract_version(deps.as_mut().storage, "crates.io:cw721-base", "0.18.0").unwrap();
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+    cw2::set_contract_version(
+        deps.as_mut().storage,
+        "crates.io:cw721-time-limited",
+        "0.1.0",
+    )
+    .unwrap();
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config.royalty_bps, Some(1000));
+    assert_eq!(config.royalty_percentage, None);
+    let res =
+        query_royalties_info(deps.as_ref(), "Enterprise".to_string(), Uint128::new(1000)).unwrap();
+    assert_eq!(res.royalty_amount, Uint128::new(100));
+
+    // the rate cannot be raised and changes wait for the default timelock
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::UpdateRoyaltyConfig {
+            royalty_bps: Some(2000),
+            royalty_payment_address: None,
+            royalty_recipients: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::RoyaltyAboveMax { max: 1000 });
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::UpdateRoyaltyConfig {
+            royalty_bps: Some(500),
+            royalty_payment_address: None,
+            royalty_recipients: None,
+        },
+    )
+    .unwrap();
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(
+        config.pending_royalty,
+        Some(PendingRoyalty {
+            royalty_bps: 500,
+            applicable_at: DEFAULT_ROYALTY_TIMELOCK.after(&mock_env().block),
+        })
+    );
+
+    // old tokens are listed once anyone has indexed them, a page at a time
+    let mut env = mock_env();
+    env.block.height = expires;
+    let expired = |deps: Deps| -> Vec<String> {
+        let query_msg = QueryMsg::TimeLimited {
+            msg: TimeLimitedQueryMsg::Expired {
+                start_after: None,
+                limit: None,
+            },
+        };
+        let res: ExpiringTokensResponse =
+            from_binary(&query(deps, env.clone(), query_msg).unwrap()).unwrap();
+        res.tokens.into_iter().map(|token| token.token_id).collect()
+    };
+    assert!(expired(deps.as_ref()).is_empty());
+    let index_msg = ExecuteMsg::IndexExpirations { limit: Some(1) };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        index_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(expired(deps.as_ref()), vec!["Enterprise".to_string()]);
+    for _ in 0..2 {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            index_msg.clone(),
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        expired(deps.as_ref()),
+        vec!["Enterprise".to_string(), "Voyager".to_string()]
+    );
+
+    // until every token is indexed
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        index_msg,
+    )
+    .unwrap_err();
+}
+
 #[test]
 fn check_token_expires() {
     let mut deps = mock_dependencies();
@@ -345,9 +857,10 @@ fn check_token_expires() {
         name: "SpaceShips".to_string(),
         symbol: "SPACE".to_string(),
         minter: CREATOR.to_string(),
-        royalty_percentage: Some(10),
+        royalty_bps: Some(1000),
         royalty_payment_address: Some(royalty_payment_address.clone()),
         creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
     };
     instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
 
@@ -363,6 +876,7 @@ fn check_token_expires() {
             expires: Some(Expiration::AtHeight(mock_env().block.height + 100)),
             ..Metadata::default()
         }),
+        lifetime: None,
     };
     execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
 
@@ -435,3 +949,2564 @@ fn check_token_expires() {
     let res = execute(deps.as_mut(), new_env.clone(), new_info, burn_msg);
     assert!(res.is_ok());
 }
+
+#[test]
+fn extend_token_expiration() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let token_id = "Enterprise";
+    let expires = Expiration::AtHeight(mock_env().block.height + 100);
+
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            expires: Some(expires),
+            ..Metadata::default()
+        }),
+        lifetime: None,
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+    // only the minter can extend the expiration
+    let extend_msg = ExecuteMsg::ExtendExpiration {
+        token_id: token_id.to_string(),
+        expires: Expiration::AtHeight(mock_env().block.height + 200),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        extend_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+
+    // the new expiration must be of the same kind
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::ExtendExpiration {
+            token_id: token_id.to_string(),
+            expires: Expiration::AtTime(mock_env().block.time.plus_seconds(100)),
+        },
+    );
+    assert_eq!(res.unwrap_err(), ContractError::ExpirationKindMismatch {});
+
+    // the new expiration must be later than the current one
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::ExtendExpiration {
+            token_id: token_id.to_string(),
+            expires,
+        },
+    );
+    assert_eq!(res.unwrap_err(), ContractError::ExpirationNotExtended {});
+
+    let res = execute(deps.as_mut(), mock_env(), info, extend_msg).unwrap();
+    let renew_event = res.events.iter().find(|e| e.ty == "renew").unwrap();
+    assert_eq!(
+        renew_event.attributes[1].value,
+        Expiration::AtHeight(mock_env().block.height + 100).to_string()
+    );
+
+    let token_info = contract
+        .nft_info(deps.as_ref(), token_id.to_string())
+        .unwrap();
+    assert_eq!(
+        token_info.extension.unwrap().expires,
+        Some(Expiration::AtHeight(mock_env().block.height + 200))
+    );
+
+    // the token can still be transferred after its original expiration
+    let mut new_env = mock_env();
+    new_env.block.height += 101;
+    let transfer_msg = ExecuteMsg::TransferNft {
+        recipient: "picard".to_string(),
+        token_id: token_id.to_string(),
+    };
+    execute(
+        deps.as_mut(),
+        new_env,
+        mock_info("jeanluc", &[]),
+        transfer_msg,
+    )
+    .unwrap();
+}
+
+#[test]
+fn mint_with_relative_lifetime() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    // the lifetime is converted against the block the mint is executed in
+    let mut env = mock_env();
+    env.block.height += 50;
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Enterprise".to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: None,
+        lifetime: Some(Duration::Height(100)),
+    };
+    execute(deps.as_mut(), env.clone(), info.clone(), exec_msg).unwrap();
+
+    let token_info = contract
+        .nft_info(deps.as_ref(), "Enterprise".to_string())
+        .unwrap();
+    assert_eq!(
+        token_info.extension.unwrap().expires,
+        Some(Expiration::AtHeight(env.block.height + 100))
+    );
+
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Voyager".to_string(),
+        owner: "janeway".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            name: Some("Starship USS Voyager".to_string()),
+            ..Metadata::default()
+        }),
+        lifetime: Some(Duration::Time(3600)),
+    };
+    execute(deps.as_mut(), env.clone(), info.clone(), exec_msg).unwrap();
+
+    let token_info = contract
+        .nft_info(deps.as_ref(), "Voyager".to_string())
+        .unwrap();
+    let extension = token_info.extension.unwrap();
+    assert_eq!(extension.name, Some("Starship USS Voyager".to_string()));
+    assert_eq!(
+        extension.expires,
+        Some(Expiration::AtTime(env.block.time.plus_seconds(3600)))
+    );
+
+    // a lifetime cannot be combined with an absolute expiration
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Defiant".to_string(),
+        owner: "sisko".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            expires: Some(Expiration::AtHeight(env.block.height + 10)),
+            ..Metadata::default()
+        }),
+        lifetime: Some(Duration::Height(100)),
+    };
+    let res = execute(deps.as_mut(), env, info, exec_msg);
+    assert_eq!(res.unwrap_err(), ContractError::ConflictingExpiration {});
+}
+
+#[test]
+fn enforce_collection_lifetime_config() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let lifetime = LifetimeConfig {
+        default_lifetime: Some(Duration::Height(100)),
+        min_lifetime: Some(Duration::Height(10)),
+        max_lifetime: Some(Duration::Height(1000)),
+    };
+
+    // the default lifetime must lie within the bounds
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        lifetime: Some(LifetimeConfig {
+            default_lifetime: Some(Duration::Height(5)),
+            ..lifetime.clone()
+        }),
+        ..InstantiateMsg::default()
+    };
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg.clone());
+    assert!(res.is_err());
+
+    let init_msg = InstantiateMsg {
+        lifetime: Some(lifetime.clone()),
+        ..init_msg
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let query_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::LifetimeConfig {},
+    };
+    let res: LifetimeConfig =
+        from_binary(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+    assert_eq!(res, lifetime);
+
+    // a token minted without an expiration gets the default lifetime
+    let mint = |token_id: &str, expires: Option<Expiration>, lifetime: Option<Duration>| {
+        ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                expires,
+                ..Metadata::default()
+            }),
+            lifetime,
+        }
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint("Enterprise", None, None),
+    )
+    .unwrap();
+    let token_info = contract
+        .nft_info(deps.as_ref(), "Enterprise".to_string())
+        .unwrap();
+    assert_eq!(
+        token_info.extension.unwrap().expires,
+        Some(Expiration::AtHeight(mock_env().block.height + 100))
+    );
+
+    // lifetimes outside the bounds or of another kind are rejected
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint("Voyager", None, Some(Duration::Height(5))),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::LifetimeTooShort {
+            min: Duration::Height(10).to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint("Voyager", Some(Expiration::Never {}), None),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::LifetimeTooLong {
+            max: Duration::Height(1000).to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint("Voyager", None, Some(Duration::Time(3600))),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::LifetimeKindMismatch {
+            bound: Duration::Height(10).to_string()
+        }
+    );
+
+    // renewals are bounded too
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::ExtendExpiration {
+            token_id: "Enterprise".to_string(),
+            expires: Expiration::AtHeight(mock_env().block.height + 1001),
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::LifetimeTooLong {
+            max: Duration::Height(1000).to_string()
+        }
+    );
+
+    // only the minter can update the settings
+    let update_msg = ExecuteMsg::UpdateLifetimeConfig {
+        default_lifetime: None,
+        min_lifetime: None,
+        max_lifetime: Some(Duration::Height(2000)),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        update_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+    execute(deps.as_mut(), mock_env(), info.clone(), update_msg).unwrap();
+
+    let res: LifetimeConfig =
+        from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+    assert_eq!(res.max_lifetime, Some(Duration::Height(2000)));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ExtendExpiration {
+            token_id: "Enterprise".to_string(),
+            expires: Expiration::AtHeight(mock_env().block.height + 1001),
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn validate_expiration_at_mint() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        allowed_expiration: Some(ExpirationKinds::Height),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let mint = |token_id: &str, expires: Option<Expiration>| ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            expires,
+            ..Metadata::default()
+        }),
+        lifetime: None,
+    };
+
+    // a token cannot be dead on arrival
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint(
+            "Enterprise",
+            Some(Expiration::AtHeight(mock_env().block.height)),
+        ),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::AlreadyExpired {});
+
+    // only height based expirations are allowed in this collection
+    let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(100));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint("Enterprise", Some(expires)),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::ExpirationKindNotAllowed {
+            expires: expires.to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint("Enterprise", None),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::ExpirationKindNotAllowed {
+            expires: Expiration::Never {}.to_string()
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        mint(
+            "Enterprise",
+            Some(Expiration::AtHeight(mock_env().block.height + 1)),
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn activate_token_on_first_claim() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        pending_transfers: Some(PendingTransfers::MinterOnly),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let token_id = "Enterprise";
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            pending_lifetime: Some(Duration::Height(100)),
+            ..Metadata::default()
+        }),
+        lifetime: None,
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+    let status_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::TokenStatus {
+            token_id: token_id.to_string(),
+        },
+    };
+    let res: TokenStatusResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), status_msg.clone()).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        TokenStatusResponse {
+            status: TokenStatus::PendingActivation,
+            expires: None,
+            remaining: Some(Duration::Height(100)),
+        }
+    );
+
+    // the token stays with its first owner until activated
+    let transfer_msg = ExecuteMsg::TransferNft {
+        recipient: "picard".to_string(),
+        token_id: token_id.to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        transfer_msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::TokenNotActivated {});
+
+    // only the owner can activate the token
+    let activate_msg = ExecuteMsg::Activate {
+        token_id: token_id.to_string(),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, activate_msg.clone());
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+
+    // the lifetime starts at activation
+    let mut env = mock_env();
+    env.block.height += 1000;
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("jeanluc", &[]),
+        activate_msg.clone(),
+    )
+    .unwrap();
+    let token_info = contract
+        .nft_info(deps.as_ref(), token_id.to_string())
+        .unwrap();
+    let extension = token_info.extension.unwrap();
+    assert_eq!(
+        extension.expires,
+        Some(Expiration::AtHeight(env.block.height + 100))
+    );
+    assert_eq!(extension.pending_lifetime, None);
+    assert_eq!(
+        EXPIRY_BY_HEIGHT
+            .keys(deps.as_ref().storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        vec![(env.block.height + 100, token_id.to_string())]
+    );
+
+    // a token can only be activated once
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("jeanluc", &[]),
+        activate_msg,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::NotPendingActivation {
+            token_id: token_id.to_string()
+        }
+    );
+
+    execute(deps.as_mut(), env, mock_info("jeanluc", &[]), transfer_msg).unwrap();
+}
+
+#[test]
+fn respect_validity_window() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        not_yet_valid_transfers: Some(PendingTransfers::MinterOnly),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let height = mock_env().block.height;
+    let mint = |valid_from: u64, expires: u64| ExecuteMsg::Mint {
+        token_id: "Enterprise".to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            valid_from: Some(Scheduled::AtHeight(valid_from)),
+            expires: Some(Expiration::AtHeight(expires)),
+            ..Metadata::default()
+        }),
+        lifetime: None,
+    };
+
+    // the window must start before the token expires
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint(height + 200, height + 200),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::InvalidValidityWindow {});
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint(height + 100, height + 200),
+    )
+    .unwrap();
+
+    let status_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::TokenStatus {
+            token_id: "Enterprise".to_string(),
+        },
+    };
+    let res: TokenStatusResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), status_msg.clone()).unwrap()).unwrap();
+    assert_eq!(res.status, TokenStatus::NotYetValid);
+
+    // only the minter can move the token before the window starts
+    let transfer_msg = |recipient: &str| ExecuteMsg::TransferNft {
+        recipient: recipient.to_string(),
+        token_id: "Enterprise".to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        transfer_msg("picard"),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::TokenNotYetValid {});
+    execute(deps.as_mut(), mock_env(), info, transfer_msg("picard")).unwrap();
+
+    // inside the window the token is active and can be moved by its owner
+    let mut env = mock_env();
+    env.block.height += 100;
+    let res: TokenStatusResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), status_msg.clone()).unwrap()).unwrap();
+    assert_eq!(res.status, TokenStatus::Active);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("picard", &[]),
+        transfer_msg("riker"),
+    )
+    .unwrap();
+
+    // the window ends when the token expires
+    env.block.height += 100;
+    let res: TokenStatusResponse =
+        from_binary(&query(deps.as_ref(), env, status_msg).unwrap()).unwrap();
+    assert_eq!(res.status, TokenStatus::Expired);
+}
+
+#[test]
+fn freeze_and_unfreeze_expiry_clock() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    // the clock cannot be frozen at mint
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Voyager".to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            frozen: Some(FrozenClock {
+                height: mock_env().block.height,
+                time: mock_env().block.time,
+            }),
+            ..Metadata::default()
+        }),
+        lifetime: Some(Duration::Height(100)),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap_err();
+
+    let token_id = "Enterprise";
+    let height = mock_env().block.height;
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: None,
+        lifetime: Some(Duration::Height(100)),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+    // only the minter can freeze the clock
+    let freeze_msg = ExecuteMsg::FreezeExpiry {
+        token_id: token_id.to_string(),
+    };
+    let mut env = mock_env();
+    env.block.height += 40;
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("jeanluc", &[]),
+        freeze_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), freeze_msg.clone()).unwrap();
+    assert!(res.events.iter().any(|e| e.ty == "freeze_expiry"));
+    let res = execute(deps.as_mut(), env, info.clone(), freeze_msg);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::AlreadyFrozen {
+            token_id: token_id.to_string()
+        }
+    );
+
+    // while frozen the token is active past its expiration and out of the index
+    let mut env = mock_env();
+    env.block.height += 150;
+    let status_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::TokenStatus {
+            token_id: token_id.to_string(),
+        },
+    };
+    let res: TokenStatusResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), status_msg.clone()).unwrap()).unwrap();
+    assert_eq!(res.status, TokenStatus::Active);
+    assert_eq!(res.remaining, Some(Duration::Height(60)));
+    assert!(EXPIRY_BY_HEIGHT.is_empty(deps.as_ref().storage));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("jeanluc", &[]),
+        ExecuteMsg::TransferNft {
+            recipient: "picard".to_string(),
+            token_id: token_id.to_string(),
+        },
+    )
+    .unwrap();
+
+    // unfreezing credits the frozen blocks back
+    let unfreeze_msg = ExecuteMsg::UnfreezeExpiry {
+        token_id: token_id.to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        unfreeze_msg.clone(),
+    )
+    .unwrap();
+    let unfreeze_event = res
+        .events
+        .iter()
+        .find(|e| e.ty == "unfreeze_expiry")
+        .unwrap();
+    assert_eq!(
+        unfreeze_event.attributes[2].value,
+        Expiration::AtHeight(height + 210).to_string()
+    );
+    let token_info = contract
+        .nft_info(deps.as_ref(), token_id.to_string())
+        .unwrap();
+    assert_eq!(
+        token_info.extension.unwrap().expires,
+        Some(Expiration::AtHeight(height + 210))
+    );
+    let res: TokenStatusResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), status_msg).unwrap()).unwrap();
+    assert_eq!(res.remaining, Some(Duration::Height(60)));
+    assert_eq!(
+        EXPIRY_BY_HEIGHT
+            .keys(deps.as_ref().storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        vec![(height + 210, token_id.to_string())]
+    );
+
+    let res = execute(deps.as_mut(), env, info, unfreeze_msg);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::NotFrozen {
+            token_id: token_id.to_string()
+        }
+    );
+}
+
+#[test]
+fn consume_usage_limited_token() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let token_id = "Enterprise";
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            max_uses: Some(10),
+            ..Metadata::default()
+        }),
+        lifetime: Some(Duration::Time(90 * 24 * 60 * 60)),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    let token_info = contract
+        .nft_info(deps.as_ref(), token_id.to_string())
+        .unwrap();
+    assert_eq!(token_info.extension.unwrap().uses_remaining, Some(10));
+
+    // only the owner or an authorized consumer can consume uses
+    let consume_msg = |amount: u32| ExecuteMsg::Consume {
+        token_id: token_id.to_string(),
+        amount,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("quark", &[]),
+        consume_msg(1),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateConsumers {
+            add: vec!["quark".to_string()],
+            remove: vec![],
+        },
+    )
+    .unwrap();
+    let res: ConsumersResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TimeLimited {
+                msg: TimeLimitedQueryMsg::Consumers {
+                    start_after: None,
+                    limit: None,
+                },
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.consumers, vec!["quark".to_string()]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("quark", &[]),
+        consume_msg(3),
+    )
+    .unwrap();
+    let consume_event = res.events.iter().find(|e| e.ty == "consume").unwrap();
+    assert_eq!(consume_event.attributes[2].value, "7");
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        consume_msg(6),
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        consume_msg(2),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::NotEnoughUses { uses_remaining: 1 }
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        consume_msg(1),
+    )
+    .unwrap();
+
+    // a used up token is treated like an expired one
+    let res: TokenStatusResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TimeLimited {
+                msg: TimeLimitedQueryMsg::TokenStatus {
+                    token_id: token_id.to_string(),
+                },
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.status, TokenStatus::UsedUp);
+
+    let transfer_msg = ExecuteMsg::TransferNft {
+        recipient: "picard".to_string(),
+        token_id: token_id.to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        transfer_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+    execute(deps.as_mut(), mock_env(), info, transfer_msg).unwrap();
+}
+
+#[test]
+fn redeem_ticket_with_scanner() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    for (token_id, lifetime) in [("Enterprise", 100), ("Voyager", 10)] {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: None,
+            lifetime: Some(Duration::Height(lifetime)),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+
+    // a token cannot be minted already redeemed
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Defiant".to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            redemption: Some(Redemption {
+                scanner: Addr::unchecked("odo"),
+                height: mock_env().block.height,
+                time: mock_env().block.time,
+            }),
+            ..Metadata::default()
+        }),
+        lifetime: Some(Duration::Height(100)),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap_err();
+
+    // only scanners managed by the minter can redeem
+    let redeem_msg = |token_id: &str| ExecuteMsg::Redeem {
+        token_id: token_id.to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("odo", &[]),
+        redeem_msg("Enterprise"),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::NotScanner {});
+    let scanners_msg = ExecuteMsg::UpdateScanners {
+        add: vec!["odo".to_string()],
+        remove: vec![],
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("odo", &[]),
+        scanners_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+    execute(deps.as_mut(), mock_env(), info, scanners_msg).unwrap();
+    let res: ScannersResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TimeLimited {
+                msg: TimeLimitedQueryMsg::Scanners {
+                    start_after: None,
+                    limit: None,
+                },
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.scanners, vec!["odo".to_string()]);
+
+    let mut env = mock_env();
+    env.block.height += 20;
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("odo", &[]),
+        redeem_msg("Enterprise"),
+    )
+    .unwrap();
+
+    let redemption_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::Redemption {
+            token_id: "Enterprise".to_string(),
+        },
+    };
+    let res: RedemptionResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), redemption_msg).unwrap()).unwrap();
+    assert_eq!(
+        res.redemption,
+        Some(Redemption {
+            scanner: Addr::unchecked("odo"),
+            height: env.block.height,
+            time: env.block.time,
+        })
+    );
+
+    // a ticket can only be redeemed once and only until it expires
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("odo", &[]),
+        redeem_msg("Enterprise"),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::AlreadyRedeemed {
+            token_id: "Enterprise".to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info("odo", &[]),
+        redeem_msg("Voyager"),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::TokenExpired {
+            token_id: "Voyager".to_string()
+        }
+    );
+}
+
+#[test]
+fn enforce_transferability_policy() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        transferability: Some(Transferability::Never),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let tokens = [
+        ("Enterprise", None),
+        ("Voyager", Some(Transferability::Always)),
+        ("Defiant", Some(Transferability::ExpiredToMinter)),
+        ("Discovery", Some(Transferability::ExpiredToMinter)),
+    ];
+    for (token_id, transferability) in tokens {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                transferability,
+                ..Metadata::default()
+            }),
+            lifetime: Some(Duration::Height(100)),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+    let transfer_msg = |token_id: &str| ExecuteMsg::TransferNft {
+        recipient: "picard".to_string(),
+        token_id: token_id.to_string(),
+    };
+
+    // the owner can neither transfer nor approve a soulbound token
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        transfer_msg("Enterprise"),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::NonTransferable {});
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        ExecuteMsg::Approve {
+            spender: "picard".to_string(),
+            token_id: "Enterprise".to_string(),
+            expires: None,
+        },
+    );
+    assert_eq!(res.unwrap_err(), ContractError::NonTransferable {});
+
+    // but the minter can revoke it
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        transfer_msg("Enterprise"),
+    )
+    .unwrap();
+
+    // a token can override the policy of the collection
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        transfer_msg("Voyager"),
+    )
+    .unwrap();
+
+    // this one can only be moved by the minter once expired
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        transfer_msg("Defiant"),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::NonTransferable {});
+
+    // though the minter can still revoke it before
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Burn {
+            token_id: "Discovery".to_string(),
+        },
+    )
+    .unwrap();
+    let mut env = mock_env();
+    env.block.height += 100;
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("jeanluc", &[]),
+        transfer_msg("Defiant"),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+
+    // once expired, its holder can hand it back to the minter
+    let return_msg = |token_id: &str| ExecuteMsg::TransferNft {
+        recipient: CREATOR.to_string(),
+        token_id: token_id.to_string(),
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("jeanluc", &[]),
+        return_msg("Defiant"),
+    )
+    .unwrap();
+
+    // which the holder of a token that is never transferable cannot
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("picard", &[]),
+        return_msg("Enterprise"),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+    execute(deps.as_mut(), env, info, transfer_msg("Defiant")).unwrap();
+}
+
+#[test]
+fn renew_token_with_native_payment() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        renewal_period: Some(Duration::Height(100)),
+        renewal_price: Some(coin(10, "uaura")),
+        treasury: Some("treasury".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let token_id = "Enterprise";
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            expires: Some(Expiration::AtHeight(mock_env().block.height + 100)),
+            ..Metadata::default()
+        }),
+        lifetime: None,
+    };
+    execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+    let renew_msg = ExecuteMsg::Renew {
+        token_id: token_id.to_string(),
+    };
+
+    // only the owner can renew
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("picard", &coins(10, "uaura")),
+        renew_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+
+    // payment must cover at least one period
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &coins(9, "uaura")),
+        renew_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InsufficientPayment {
+            price: "10uaura".to_string()
+        }
+    );
+
+    // two whole periods are paid for, the remainder is refunded
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &coins(25, "uaura")),
+        renew_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(20, "uaura"),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "jeanluc".to_string(),
+                amount: coins(5, "uaura"),
+            }),
+        ]
+    );
+    let token_info = contract
+        .nft_info(deps.as_ref(), token_id.to_string())
+        .unwrap();
+    assert_eq!(
+        token_info.extension.unwrap().expires,
+        Some(Expiration::AtHeight(mock_env().block.height + 300))
+    );
+
+    // a payment too large to extend by is rejected instead of overflowing
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &coins(u128::MAX, "uaura")),
+        renew_msg.clone(),
+    );
+    assert!(matches!(
+        res.unwrap_err(),
+        ContractError::Std(StdError::ConversionOverflow { .. })
+    ));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &coins(10 * u128::from(u64::MAX), "uaura")),
+        renew_msg.clone(),
+    );
+    assert!(matches!(
+        res.unwrap_err(),
+        ContractError::Std(StdError::Overflow { .. })
+    ));
+
+    // an expired token cannot be renewed by its owner
+    let mut new_env = mock_env();
+    new_env.block.height += 300;
+    let res = execute(
+        deps.as_mut(),
+        new_env,
+        mock_info("jeanluc", &coins(10, "uaura")),
+        renew_msg,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::TokenExpired {
+            token_id: token_id.to_string()
+        }
+    );
+}
+
+#[test]
+fn pay_with_cw20_for_mint_and_renewal() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        renewal_period: Some(Duration::Time(3600)),
+        treasury: Some("treasury".to_string()),
+        cw20_prices: Some(vec![Cw20Price {
+            address: "stablecoin".to_string(),
+            mint_amount: Some(Uint128::new(50)),
+            renewal_amount: Some(Uint128::new(10)),
+        }]),
+        ..InstantiateMsg::default()
+    };
+
+    // a zero CW20 price is rejected
+    let zero_price_msg = InstantiateMsg {
+        cw20_prices: Some(vec![Cw20Price {
+            address: "stablecoin".to_string(),
+            mint_amount: Some(Uint128::new(50)),
+            renewal_amount: Some(Uint128::zero()),
+        }]),
+        ..init_msg.clone()
+    };
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), zero_price_msg);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Std(StdError::generic_err("CW20 prices must be non-zero"))
+    );
+
+    instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+    let token_id = "Enterprise";
+    let mint_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "jeanluc".to_string(),
+        amount: Uint128::new(50),
+        msg: to_binary(&ReceiveMsg::Mint {
+            token_id: token_id.to_string(),
+            token_uri: None,
+            extension: None,
+        })
+        .unwrap(),
+    });
+
+    // only allowlisted CW20 contracts are accepted
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("counterfeit", &[]),
+        mint_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Cw20NotAccepted {
+            address: "counterfeit".to_string()
+        }
+    );
+
+    // buyers cannot set what only the minter can
+    let buyer_mint_msg = |extension: Metadata| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "jeanluc".to_string(),
+            amount: Uint128::new(50),
+            msg: to_binary(&ReceiveMsg::Mint {
+                token_id: "Voyager".to_string(),
+                token_uri: None,
+                extension: Some(extension),
+            })
+            .unwrap(),
+        })
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("stablecoin", &[]),
+        buyer_mint_msg(Metadata {
+            expired_image: Some("ipfs://wreck.png".to_string()),
+            ..Metadata::default()
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::MinterOnlyMetadata {
+            field: "expired_image".to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("stablecoin", &[]),
+        buyer_mint_msg(Metadata {
+            max_uses: Some(1000),
+            ..Metadata::default()
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::MinterOnlyMetadata {
+            field: "max_uses".to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("stablecoin", &[]),
+        buyer_mint_msg(Metadata {
+            valid_from: Some(Scheduled::AtHeight(mock_env().block.height + 1000)),
+            ..Metadata::default()
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::MinterOnlyMetadata {
+            field: "valid_from".to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("stablecoin", &[]),
+        buyer_mint_msg(Metadata {
+            transferability: Some(Transferability::Always),
+            ..Metadata::default()
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::MinterOnlyMetadata {
+            field: "transferability".to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("stablecoin", &[]),
+        buyer_mint_msg(Metadata {
+            royalty_recipients: Some(vec![RoyaltyRecipient {
+                address: "jeanluc".to_string(),
+                share_bps: 10000,
+            }]),
+            ..Metadata::default()
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::MinterOnlyMetadata {
+            field: "royalty_recipients".to_string()
+        }
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("stablecoin", &[]),
+        mint_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "stablecoin".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "treasury".to_string(),
+                amount: Uint128::new(50),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    let owner = contract
+        .owner_of(deps.as_ref(), mock_env(), token_id.to_string(), false)
+        .unwrap();
+    assert_eq!(owner.owner, "jeanluc".to_string());
+    let token_info = contract
+        .nft_info(deps.as_ref(), token_id.to_string())
+        .unwrap();
+    assert_eq!(
+        token_info.extension.unwrap().expires,
+        Some(Expiration::AtTime(mock_env().block.time.plus_seconds(3600)))
+    );
+
+    // renew for one period, the remainder is refunded
+    let renew_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "jeanluc".to_string(),
+        amount: Uint128::new(15),
+        msg: to_binary(&ReceiveMsg::Renew {
+            token_id: token_id.to_string(),
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("stablecoin", &[]),
+        renew_msg,
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    let token_info = contract
+        .nft_info(deps.as_ref(), token_id.to_string())
+        .unwrap();
+    assert_eq!(
+        token_info.extension.unwrap().expires,
+        Some(Expiration::AtTime(mock_env().block.time.plus_seconds(7200)))
+    );
+}
+
+#[test]
+fn renew_during_grace_period() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        renewal_period: Some(Duration::Height(100)),
+        renewal_price: Some(coin(10, "uaura")),
+        grace_period: Some(Duration::Height(50)),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let token_id = "Enterprise";
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: token_id.to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            expires: Some(Expiration::AtHeight(mock_env().block.height + 100)),
+            ..Metadata::default()
+        }),
+        lifetime: None,
+    };
+    execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+    // during the grace period nobody can move the token
+    let mut grace_env = mock_env();
+    grace_env.block.height += 120;
+    let transfer_msg = ExecuteMsg::TransferNft {
+        recipient: "picard".to_string(),
+        token_id: token_id.to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        grace_env.clone(),
+        mock_info("jeanluc", &[]),
+        transfer_msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::TokenInGracePeriod {});
+    let res = execute(
+        deps.as_mut(),
+        grace_env.clone(),
+        mock_info(CREATOR, &[]),
+        transfer_msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::TokenInGracePeriod {});
+    let approve_msg = ExecuteMsg::Approve {
+        spender: "picard".to_string(),
+        token_id: token_id.to_string(),
+        expires: None,
+    };
+    let res = execute(
+        deps.as_mut(),
+        grace_env.clone(),
+        mock_info("jeanluc", &[]),
+        approve_msg,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::TokenInGracePeriod {});
+
+    // nor is it listed as expired yet
+    let expired_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::Expired {
+            start_after: None,
+            limit: None,
+        },
+    };
+    let res: ExpiringTokensResponse =
+        from_binary(&query(deps.as_ref(), grace_env.clone(), expired_msg.clone()).unwrap())
+            .unwrap();
+    assert!(res.tokens.is_empty());
+
+    // but the owner can still renew it
+    let renew_msg = ExecuteMsg::Renew {
+        token_id: token_id.to_string(),
+    };
+    execute(
+        deps.as_mut(),
+        grace_env.clone(),
+        mock_info("jeanluc", &coins(10, "uaura")),
+        renew_msg.clone(),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        grace_env,
+        mock_info("jeanluc", &[]),
+        transfer_msg,
+    )
+    .unwrap();
+
+    // after the grace period the token cannot be renewed anymore
+    let mut expired_env = mock_env();
+    expired_env.block.height += 250;
+    let res = execute(
+        deps.as_mut(),
+        expired_env.clone(),
+        mock_info("picard", &coins(10, "uaura")),
+        renew_msg,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::TokenExpired {
+            token_id: token_id.to_string()
+        }
+    );
+
+    // and it is listed as expired
+    let res: ExpiringTokensResponse =
+        from_binary(&query(deps.as_ref(), expired_env, expired_msg).unwrap()).unwrap();
+    assert_eq!(
+        res.tokens,
+        vec![ExpiringToken {
+            token_id: token_id.to_string(),
+            owner: "picard".to_string(),
+            expires: Expiration::AtHeight(mock_env().block.height + 200),
+        }]
+    );
+}
+
+#[test]
+fn reclaim_expired_tokens() {
+    let mut deps = mock_dependencies();
+    let contract = Cw721TimeLimited::default();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        reclaim_vault: Some("vault".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    // two tokens expire at height + 100, one at height + 1000
+    for (token_id, lifetime) in [("Enterprise", 100), ("Voyager", 1000), ("Defiant", 100)] {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                expires: Some(Expiration::AtHeight(mock_env().block.height + lifetime)),
+                ..Metadata::default()
+            }),
+            lifetime: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+    // and one already held by the vault expires first
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Archive".to_string(),
+        owner: "vault".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            expires: Some(Expiration::AtHeight(mock_env().block.height + 50)),
+            ..Metadata::default()
+        }),
+        lifetime: None,
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    let approve_msg = ExecuteMsg::Approve {
+        spender: "picard".to_string(),
+        token_id: "Enterprise".to_string(),
+        expires: None,
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        approve_msg,
+    )
+    .unwrap();
+
+    let mut new_env = mock_env();
+    new_env.block.height += 101;
+
+    // only the minter can reclaim
+    let reclaim_msg = ExecuteMsg::ReclaimExpired {
+        token_ids: None,
+        limit: None,
+    };
+    let res = execute(
+        deps.as_mut(),
+        new_env.clone(),
+        mock_info("jeanluc", &[]),
+        reclaim_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+
+    // the still active token is skipped when listed explicitly
+    let res = execute(
+        deps.as_mut(),
+        new_env.clone(),
+        info.clone(),
+        ExecuteMsg::ReclaimExpired {
+            token_ids: Some(vec!["Voyager".to_string(), "Defiant".to_string()]),
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.events.len(), 1);
+    assert_eq!(res.events[0].attributes[0].value, "Defiant".to_string());
+
+    // a scan only walks up to limit tokens, the one the vault holds just leaves the index
+    let res = execute(
+        deps.as_mut(),
+        new_env.clone(),
+        info.clone(),
+        ExecuteMsg::ReclaimExpired {
+            token_ids: None,
+            limit: Some(1),
+        },
+    )
+    .unwrap();
+    assert!(res.events.is_empty());
+
+    // the remaining expired token is found by the next scan
+    let res = execute(deps.as_mut(), new_env.clone(), info, reclaim_msg).unwrap();
+    assert_eq!(res.events.len(), 1);
+    assert_eq!(res.events[0].attributes[0].value, "Enterprise".to_string());
+
+    // reclaimed tokens leave the index, so later scans do not walk them again
+    let expired = expired_token_ids(
+        &deps.storage,
+        &CONFIG.load(&deps.storage).unwrap(),
+        &new_env.block,
+    )
+    .collect::<StdResult<Vec<_>>>()
+    .unwrap();
+    assert!(expired.is_empty());
+
+    let owner = contract
+        .owner_of(deps.as_ref(), mock_env(), "Enterprise".to_string(), true)
+        .unwrap();
+    assert_eq!(owner.owner, "vault".to_string());
+    assert!(owner.approvals.is_empty());
+    let owner = contract
+        .owner_of(deps.as_ref(), mock_env(), "Voyager".to_string(), true)
+        .unwrap();
+    assert_eq!(owner.owner, "jeanluc".to_string());
+}
+
+#[test]
+fn cleanup_expired_tokens_for_bounty() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        post_expiry: Some(PostExpiryPolicy::Burn),
+        cleanup_bounty: Some(coin(3, "uaura")),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    for (token_id, lifetime) in [("Enterprise", 100), ("Voyager", 1000), ("Defiant", 100)] {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                expires: Some(Expiration::AtHeight(mock_env().block.height + lifetime)),
+                ..Metadata::default()
+            }),
+            lifetime: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &coins(5, "uaura")),
+        ExecuteMsg::FundBountyPool {},
+    )
+    .unwrap();
+
+    let mut new_env = mock_env();
+    new_env.block.height += 101;
+
+    // unexpired tokens are rejected
+    let res = execute(
+        deps.as_mut(),
+        new_env.clone(),
+        mock_info("riker", &[]),
+        ExecuteMsg::CleanupExpired {
+            token_ids: vec!["Voyager".to_string()],
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::TokenNotExpired {
+            token_id: "Voyager".to_string()
+        }
+    );
+
+    // anyone can burn expired tokens, the bounty is capped by the pool
+    let res = execute(
+        deps.as_mut(),
+        new_env,
+        mock_info("riker", &[]),
+        ExecuteMsg::CleanupExpired {
+            token_ids: vec!["Enterprise".to_string(), "Defiant".to_string()],
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "riker".to_string(),
+            amount: coins(5, "uaura"),
+        })]
+    );
+
+    let count = Cw721TimeLimited::default()
+        .num_tokens(deps.as_ref())
+        .unwrap()
+        .count;
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn apply_post_expiry_policy() {
+    let setup = |post_expiry: PostExpiryPolicy| {
+        let mut deps = mock_dependencies();
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            royalty_bps: None,
+            royalty_payment_address: None,
+            creator: Some("creator".to_string()),
+            post_expiry: Some(post_expiry),
+            ..InstantiateMsg::default()
+        };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: "Enterprise".to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: None,
+            lifetime: Some(Duration::Height(100)),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        deps
+    };
+    let mut env = mock_env();
+    env.block.height += 100;
+    let transfer_msg = ExecuteMsg::TransferNft {
+        recipient: "picard".to_string(),
+        token_id: "Enterprise".to_string(),
+    };
+    let cleanup_msg = ExecuteMsg::CleanupExpired {
+        token_ids: vec!["Enterprise".to_string()],
+    };
+
+    // the policy is queryable
+    let deps = setup(PostExpiryPolicy::Freeze);
+    let res: PostExpiryPolicy = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TimeLimited {
+                msg: TimeLimitedQueryMsg::PostExpiryPolicy {},
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res, PostExpiryPolicy::Freeze);
+
+    // a frozen souvenir cannot be moved or cleaned up by anyone
+    let mut deps = setup(PostExpiryPolicy::Freeze);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        transfer_msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::PostExpiryRestricted {});
+
+    // not even if it is soulbound
+    let exec_msg = ExecuteMsg::Mint {
+        token_id: "Voyager".to_string(),
+        owner: "jeanluc".to_string(),
+        token_uri: None,
+        extension: Some(Metadata {
+            transferability: Some(Transferability::Never),
+            ..Metadata::default()
+        }),
+        lifetime: Some(Duration::Height(100)),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), exec_msg).unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::Burn {
+            token_id: "Voyager".to_string(),
+        },
+    );
+    assert_eq!(res.unwrap_err(), ContractError::PostExpiryRestricted {});
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        cleanup_msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::ExpiredTokensKept {});
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::ReclaimExpired {
+            token_ids: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(res.events.is_empty());
+
+    // reverting to the creator needs a creator tokens can be sent to
+    for creator in [None, Some("Creator".to_string())] {
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            creator,
+            post_expiry: Some(PostExpiryPolicy::RevertToCreator),
+            ..InstantiateMsg::default()
+        };
+        instantiate(
+            mock_dependencies().as_mut(),
+            mock_env(),
+            mock_info(CREATOR, &[]),
+            init_msg,
+        )
+        .unwrap_err();
+    }
+
+    // an expired token reverts to the creator
+    let mut deps = setup(PostExpiryPolicy::RevertToCreator);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        transfer_msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::PostExpiryRestricted {});
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        cleanup_msg,
+    )
+    .unwrap();
+    let owner = Cw721TimeLimited::default()
+        .owner_of(deps.as_ref(), env.clone(), "Enterprise".to_string(), true)
+        .unwrap();
+    assert_eq!(owner.owner, "creator");
+
+    // an expired token can only be burned
+    let mut deps = setup(PostExpiryPolicy::Burn);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        transfer_msg,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::PostExpiryRestricted {});
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::Burn {
+            token_id: "Enterprise".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        Cw721TimeLimited::default()
+            .num_tokens(deps.as_ref())
+            .unwrap()
+            .count,
+        0
+    );
+}
+
+#[test]
+fn show_expired_metadata() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        royalty_bps: None,
+        royalty_payment_address: None,
+        creator: Some("creator".to_string()),
+        expired_token_uri: Some("ipfs://expired.json".to_string()),
+        expired_image: Some("ipfs://expired.png".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let tokens = [
+        ("Enterprise", None),
+        ("Voyager", Some("ipfs://voyager-expired.png".to_string())),
+    ];
+    for (token_id, expired_image) in tokens {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: Some(format!("ipfs://{token_id}.json")),
+            extension: Some(Metadata {
+                image: Some(format!("ipfs://{token_id}.png")),
+                expired_image,
+                ..Metadata::default()
+            }),
+            lifetime: Some(Duration::Height(100)),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+    let nft_info = |env, token_id: &str| -> NftInfoResponse<Extension> {
+        from_binary(
+            &query(
+                deps.as_ref(),
+                env,
+                QueryMsg::NftInfo {
+                    token_id: token_id.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+
+    // the stored metadata is shown while the token is active
+    let res = nft_info(mock_env(), "Enterprise");
+    assert_eq!(res.token_uri, Some("ipfs://Enterprise.json".to_string()));
+    assert_eq!(
+        res.extension.unwrap().image,
+        Some("ipfs://Enterprise.png".to_string())
+    );
+
+    // the expired metadata is shown once expired, the token's own before the collection's
+    let mut env = mock_env();
+    env.block.height += 100;
+    let res = nft_info(env.clone(), "Enterprise");
+    assert_eq!(res.token_uri, Some("ipfs://expired.json".to_string()));
+    assert_eq!(
+        res.extension.unwrap().image,
+        Some("ipfs://expired.png".to_string())
+    );
+    let res = nft_info(env.clone(), "Voyager");
+    assert_eq!(res.token_uri, Some("ipfs://expired.json".to_string()));
+    assert_eq!(
+        res.extension.unwrap().image,
+        Some("ipfs://voyager-expired.png".to_string())
+    );
+
+    let res: AllNftInfoResponse<Extension> = from_binary(
+        &query(
+            deps.as_ref(),
+            env,
+            QueryMsg::AllNftInfo {
+                token_id: "Enterprise".to_string(),
+                include_expired: Some(true),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.info.token_uri, Some("ipfs://expired.json".to_string()));
+
+    // the stored data is unchanged
+    let token = Cw721TimeLimited::default()
+        .tokens
+        .load(deps.as_ref().storage, "Enterprise")
+        .unwrap();
+    assert_eq!(token.token_uri, Some("ipfs://Enterprise.json".to_string()));
+}
+
+#[test]
+fn keep_expiration_index_in_sync() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let height = mock_env().block.height;
+    let time = mock_env().block.time;
+    for (token_id, expires) in [
+        ("Enterprise", Some(Expiration::AtHeight(height + 100))),
+        ("Voyager", Some(Expiration::AtTime(time.plus_seconds(100)))),
+        ("Defiant", None),
+    ] {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                expires,
+                ..Metadata::default()
+            }),
+            lifetime: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+    assert_eq!(
+        EXPIRY_BY_HEIGHT
+            .load(&deps.storage, (height + 100, "Enterprise"))
+            .unwrap(),
+        "jeanluc".to_string()
+    );
+    assert_eq!(
+        EXPIRY_BY_TIME
+            .load(&deps.storage, (time.plus_seconds(100).nanos(), "Voyager"))
+            .unwrap(),
+        "jeanluc".to_string()
+    );
+
+    // transfers update the owner
+    let transfer_msg = ExecuteMsg::TransferNft {
+        recipient: "picard".to_string(),
+        token_id: "Enterprise".to_string(),
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        transfer_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        EXPIRY_BY_HEIGHT
+            .load(&deps.storage, (height + 100, "Enterprise"))
+            .unwrap(),
+        "picard".to_string()
+    );
+
+    // renewals move the token in the index
+    let extend_msg = ExecuteMsg::ExtendExpiration {
+        token_id: "Enterprise".to_string(),
+        expires: Expiration::AtHeight(height + 200),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), extend_msg).unwrap();
+    let indexed = EXPIRY_BY_HEIGHT
+        .keys(&deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()
+        .unwrap();
+    assert_eq!(indexed, vec![(height + 200, "Enterprise".to_string())]);
+
+    // expired tokens are listed in expiration order
+    let mut new_env = mock_env();
+    new_env.block.height += 300;
+    new_env.block.time = new_env.block.time.plus_seconds(300);
+    let expired = expired_token_ids(
+        &deps.storage,
+        &CONFIG.load(&deps.storage).unwrap(),
+        &new_env.block,
+    )
+    .collect::<StdResult<Vec<_>>>()
+    .unwrap();
+    assert_eq!(
+        expired,
+        vec!["Enterprise".to_string(), "Voyager".to_string()]
+    );
+
+    // burning removes the token from the index
+    let burn_msg = ExecuteMsg::Burn {
+        token_id: "Voyager".to_string(),
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("jeanluc", &[]),
+        burn_msg,
+    )
+    .unwrap();
+    assert!(EXPIRY_BY_TIME.is_empty(&deps.storage));
+}
+
+#[test]
+fn query_expiring_and_expired_tokens() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let height = mock_env().block.height;
+    let time = mock_env().block.time;
+    for (token_id, expires) in [
+        ("Enterprise", Some(Expiration::AtHeight(height + 300))),
+        ("Voyager", Some(Expiration::AtHeight(height + 100))),
+        ("Defiant", Some(Expiration::AtTime(time.plus_seconds(100)))),
+        ("Reliant", None),
+    ] {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                expires,
+                ..Metadata::default()
+            }),
+            lifetime: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+
+    let query_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::ExpiringBefore {
+            at: Expiration::AtHeight(height + 500),
+            start_after: None,
+            limit: None,
+        },
+    };
+    let res: ExpiringTokensResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+    assert_eq!(
+        res.tokens,
+        vec![
+            ExpiringToken {
+                token_id: "Voyager".to_string(),
+                owner: "jeanluc".to_string(),
+                expires: Expiration::AtHeight(height + 100),
+            },
+            ExpiringToken {
+                token_id: "Enterprise".to_string(),
+                owner: "jeanluc".to_string(),
+                expires: Expiration::AtHeight(height + 300),
+            },
+        ]
+    );
+
+    // expired tokens are paged through height based ones first
+    let mut new_env = mock_env();
+    new_env.block.height += 400;
+    new_env.block.time = new_env.block.time.plus_seconds(400);
+    let mut token_ids = vec![];
+    let mut start_after = None;
+    loop {
+        let query_msg = QueryMsg::TimeLimited {
+            msg: TimeLimitedQueryMsg::Expired {
+                start_after: start_after.clone(),
+                limit: Some(1),
+            },
+        };
+        let res: ExpiringTokensResponse =
+            from_binary(&query(deps.as_ref(), new_env.clone(), query_msg).unwrap()).unwrap();
+        match res.tokens.last() {
+            Some(token) => start_after = Some(ExpiryCursor::from(token)),
+            None => break,
+        }
+        token_ids.extend(res.tokens.into_iter().map(|token| token.token_id));
+    }
+    assert_eq!(
+        token_ids,
+        vec![
+            "Voyager".to_string(),
+            "Enterprise".to_string(),
+            "Defiant".to_string()
+        ]
+    );
+
+    // paging resumes after a token that has been burned since
+    execute(
+        deps.as_mut(),
+        new_env.clone(),
+        info,
+        ExecuteMsg::Burn {
+            token_id: "Voyager".to_string(),
+        },
+    )
+    .unwrap();
+    let query_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::Expired {
+            start_after: Some(ExpiryCursor {
+                expires: Expiration::AtHeight(height + 100),
+                token_id: "Voyager".to_string(),
+            }),
+            limit: None,
+        },
+    };
+    let res: ExpiringTokensResponse =
+        from_binary(&query(deps.as_ref(), new_env, query_msg).unwrap()).unwrap();
+    assert_eq!(
+        res.tokens
+            .into_iter()
+            .map(|token| token.token_id)
+            .collect::<Vec<_>>(),
+        vec!["Enterprise".to_string(), "Defiant".to_string()]
+    );
+}
+
+#[test]
+fn query_token_status() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        grace_period: Some(Duration::Time(50)),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(100));
+    for (token_id, expires) in [("Enterprise", Some(expires)), ("Voyager", None)] {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                expires,
+                ..Metadata::default()
+            }),
+            lifetime: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+
+    let status_at = |deps: Deps, seconds: u64, token_id: &str| -> TokenStatusResponse {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        let query_msg = QueryMsg::TimeLimited {
+            msg: TimeLimitedQueryMsg::TokenStatus {
+                token_id: token_id.to_string(),
+            },
+        };
+        from_binary(&query(deps, env, query_msg).unwrap()).unwrap()
+    };
+
+    assert_eq!(
+        status_at(deps.as_ref(), 40, "Enterprise"),
+        TokenStatusResponse {
+            status: TokenStatus::Active,
+            expires: Some(expires),
+            remaining: Some(Duration::Time(60)),
+        }
+    );
+    assert_eq!(
+        status_at(deps.as_ref(), 120, "Enterprise"),
+        TokenStatusResponse {
+            status: TokenStatus::Grace,
+            expires: Some(expires),
+            remaining: Some(Duration::Time(30)),
+        }
+    );
+    assert_eq!(
+        status_at(deps.as_ref(), 150, "Enterprise"),
+        TokenStatusResponse {
+            status: TokenStatus::Expired,
+            expires: Some(expires),
+            remaining: None,
+        }
+    );
+    assert_eq!(
+        status_at(deps.as_ref(), 150, "Voyager"),
+        TokenStatusResponse {
+            status: TokenStatus::NeverExpires,
+            expires: None,
+            remaining: None,
+        }
+    );
+}
+
+#[test]
+fn hide_expired_tokens_from_ownership_queries() {
+    let mut deps = mock_dependencies();
+
+    let info = mock_info(CREATOR, &[]);
+    let init_msg = InstantiateMsg {
+        name: "SpaceShips".to_string(),
+        symbol: "SPACE".to_string(),
+        minter: CREATOR.to_string(),
+        creator: Some("creator".to_string()),
+        expired_visibility: Some(ExpiredVisibility::Error),
+        ..InstantiateMsg::default()
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    for (token_id, lifetime) in [("Enterprise", 100), ("Voyager", 1000)] {
+        let exec_msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: "jeanluc".to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                expires: Some(Expiration::AtHeight(mock_env().block.height + lifetime)),
+                ..Metadata::default()
+            }),
+            lifetime: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+    }
+
+    let mut new_env = mock_env();
+    new_env.block.height += 101;
+
+    // the expired token is reported with an error
+    let query_msg = QueryMsg::OwnerOf {
+        token_id: "Enterprise".to_string(),
+        include_expired: None,
+    };
+    let res = query(deps.as_ref(), new_env.clone(), query_msg);
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err(
+            ContractError::TokenExpired {
+                token_id: "Enterprise".to_string()
+            }
+            .to_string()
+        )
+    );
+
+    // unless include_expired is set
+    let query_msg = QueryMsg::OwnerOf {
+        token_id: "Enterprise".to_string(),
+        include_expired: Some(true),
+    };
+    let res: OwnerOfResponse =
+        from_binary(&query(deps.as_ref(), new_env.clone(), query_msg).unwrap()).unwrap();
+    assert_eq!(res.owner, "jeanluc".to_string());
+
+    // lists leave out the expired token, in the standard cw721 shape
+    let query_msg = QueryMsg::Tokens {
+        owner: "jeanluc".to_string(),
+        start_after: None,
+        limit: None,
+        include_expired: None,
+    };
+    let res: TokensResponse =
+        from_binary(&query(deps.as_ref(), new_env.clone(), query_msg).unwrap()).unwrap();
+    assert_eq!(res.tokens, vec!["Voyager".to_string()]);
+
+    let query_msg = QueryMsg::AllTokens {
+        start_after: None,
+        limit: None,
+        include_expired: Some(true),
+    };
+    let res: TokensResponse =
+        from_binary(&query(deps.as_ref(), new_env.clone(), query_msg).unwrap()).unwrap();
+    assert_eq!(
+        res.tokens,
+        vec!["Enterprise".to_string(), "Voyager".to_string()]
+    );
+
+    // only `limit` tokens are scanned per page, listing continues after them
+    let visible_msg = |start_after: Option<String>| QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::AllVisibleTokens {
+            start_after,
+            limit: Some(1),
+        },
+    };
+    let res: TokensPageResponse =
+        from_binary(&query(deps.as_ref(), new_env.clone(), visible_msg(None)).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        TokensPageResponse {
+            tokens: vec![],
+            next_start_after: Some("Enterprise".to_string()),
+        }
+    );
+    let res: TokensPageResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            new_env.clone(),
+            visible_msg(res.next_start_after),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.tokens, vec!["Voyager".to_string()]);
+    let res: TokensPageResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            new_env.clone(),
+            visible_msg(res.next_start_after),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        TokensPageResponse {
+            tokens: vec![],
+            next_start_after: None,
+        }
+    );
+
+    // the owner listing pages the same way
+    let query_msg = QueryMsg::TimeLimited {
+        msg: TimeLimitedQueryMsg::VisibleTokens {
+            owner: "jeanluc".to_string(),
+            start_after: None,
+            limit: None,
+        },
+    };
+    let res: TokensPageResponse =
+        from_binary(&query(deps.as_ref(), new_env, query_msg).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        TokensPageResponse {
+            tokens: vec!["Voyager".to_string()],
+            next_start_after: None,
+        }
+    );
+}
@@ -16,7 +16,7 @@ let deployerWallet, deployerClient, deployerAccount;
 
 const COLLECTION_NAME = "Time Limited Collection";
 const COLLECTION_SYMBOL = "TLC";
-const ROYALTY_PERCENTAGE = 10;
+const ROYALTY_BPS = 1000;
 const ROYALTY_PAYMENT_ADDRESS = "aura1fqj2redmssckrdeekhkcvd2kzp9f4nks4fctrt";
 const MINTER = "aura1fqj2redmssckrdeekhkcvd2kzp9f4nks4fctrt";
 
@@ -141,7 +141,7 @@ async function main() {
         "name": COLLECTION_NAME,
         "symbol": COLLECTION_SYMBOL,
         "minter": MINTER,
-        "royalty_percentage": ROYALTY_PERCENTAGE,
+        "royalty_bps": ROYALTY_BPS,
         "royalty_payment_address": ROYALTY_PAYMENT_ADDRESS,
         "creator": MINTER,
     };
@@ -145,9 +145,10 @@ async function main() {
                 "youtube_url": null,
                 "royalty_percentage": null,
                 "royalty_payment_address": null,
-                "expires": {
-                    "at_time": "1696909818000"
-                },
+                "expires": null,
+            },
+            "lifetime": {
+                "time": 2592000
             },
         }
     }